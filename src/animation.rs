@@ -0,0 +1,133 @@
+//! Animated textures, like water, lava and sea lanterns, which are stored as a strip of frames in a single png.
+//!
+//! The frames are laid out from top to bottom, and a .png.mcmeta file next to the texture describes the animation.
+//! A map can only show one frame, so the first frame the animation plays is used.
+
+use image::{DynamicImage, GenericImageView};
+use serde::Deserialize;
+
+/// The parts of a .png.mcmeta file needed to find the first frame of an animation.
+#[derive(Deserialize)]
+struct TextureMeta {
+    animation: Option<AnimationMeta>,
+}
+
+#[derive(Deserialize)]
+struct AnimationMeta {
+    /// The order the frames are played in, either as frame indices or as objects holding an index and a time.
+    #[serde(default)]
+    frames: Vec<FrameMeta>,
+    /// The width of each frame, the width of the texture if missing.
+    width: Option<u32>,
+    /// The height of each frame, the same as the frame width if missing.
+    height: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum FrameMeta {
+    Index(u32),
+    Timed { index: u32 },
+}
+
+/// Returns the first frame of an animated texture, using its .png.mcmeta file if there is one.
+///
+/// Textures without animation metadata that are taller than they are wide, and whose height is a multiple of their width, are treated as a strip of square frames.
+/// Any other texture is returned as it is.
+pub fn first_frame(texture: DynamicImage, mcmeta: Option<&[u8]>) -> DynamicImage {
+    let (width, height) = texture.dimensions();
+    let animation = mcmeta
+        .and_then(|bytes| serde_json::from_slice::<TextureMeta>(bytes).ok())
+        .and_then(|meta| meta.animation);
+
+    let (frame_width, frame_height, index) = match animation {
+        Some(animation) => {
+            let frame_width = animation.width.unwrap_or_else(|| width.min(height));
+            let frame_height = animation.height.unwrap_or(frame_width);
+            let index = match animation.frames.first() {
+                None => 0,
+                Some(FrameMeta::Index(index)) | Some(FrameMeta::Timed { index }) => *index,
+            };
+            (frame_width, frame_height, index)
+        }
+        None if height > width && height % width == 0 => (width, width, 0),
+        None => return texture,
+    };
+
+    if frame_width == 0 || frame_height == 0 || frame_width > width || frame_height > height {
+        return texture; // broken metadata, drawing the whole strip is better than nothing
+    }
+    let columns = width / frame_width;
+    let rows = height / frame_height;
+    let index = if index < columns * rows { index } else { 0 };
+    texture.crop_imm(
+        (index % columns) * frame_width,
+        (index / columns) * frame_height,
+        frame_width,
+        frame_height,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    /// A strip of frames, each filled with its own index as the red channel.
+    fn strip(width: u32, frame_height: u32, frames: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, frame_height * frames, |_, y| {
+            Rgba([(y / frame_height) as u8, 0, 0, 255])
+        }))
+    }
+
+    #[test]
+    fn square_textures_are_kept() {
+        let frame = first_frame(strip(16, 16, 1), None);
+        assert_eq!(frame.dimensions(), (16, 16));
+    }
+
+    #[test]
+    fn strips_without_metadata_use_the_top_frame() {
+        let frame = first_frame(strip(16, 16, 4), None);
+        assert_eq!(frame.dimensions(), (16, 16));
+        assert_eq!(frame.get_pixel(8, 8)[0], 0);
+    }
+
+    #[test]
+    fn metadata_picks_the_first_frame_played() {
+        let mcmeta = br#"{"animation": {"frames": [{"index": 2, "time": 4}, 0, 1]}}"#;
+        let frame = first_frame(strip(16, 16, 3), Some(mcmeta));
+        assert_eq!(frame.dimensions(), (16, 16));
+        assert_eq!(frame.get_pixel(8, 8)[0], 2);
+
+        let mcmeta = br#"{"animation": {"frames": [1, 0]}}"#;
+        assert_eq!(
+            first_frame(strip(16, 16, 3), Some(mcmeta)).get_pixel(0, 0)[0],
+            1
+        );
+    }
+
+    #[test]
+    fn metadata_frame_size_is_used() {
+        let mcmeta = br#"{"animation": {"height": 8}}"#;
+        let frame = first_frame(strip(16, 8, 4), Some(mcmeta));
+        assert_eq!(frame.dimensions(), (16, 8));
+        assert_eq!(frame.get_pixel(0, 0)[0], 0);
+    }
+
+    #[test]
+    fn broken_metadata_keeps_the_texture() {
+        let mcmeta = br#"{"animation": {"width": 32}}"#;
+        assert_eq!(
+            first_frame(strip(16, 16, 2), Some(mcmeta)).dimensions(),
+            (16, 32)
+        );
+
+        // frames past the end of the strip fall back to the first one.
+        let mcmeta = br#"{"animation": {"frames": [9]}}"#;
+        assert_eq!(
+            first_frame(strip(16, 16, 2), Some(mcmeta)).get_pixel(0, 0)[0],
+            0
+        );
+    }
+}
@@ -0,0 +1,209 @@
+//! Limiting a render to part of a world, so a small area of a huge world can be rendered without reading the rest of it.
+//!
+//! Regions outside of the area are never read, and chunks outside of it are skipped when the regions they are in are rendered.
+
+use crate::{ChunkCoordinate, RegionFile};
+
+/// A shape in block coordinates that a render can be limited to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AreaShape {
+    /// A rectangle of blocks, from its north west corner to its south east corner, both included.
+    Rectangle {
+        /// The smallest x and z block coordinate in the rectangle.
+        min: (i32, i32),
+        /// The largest x and z block coordinate in the rectangle.
+        max: (i32, i32),
+    },
+    /// Every block within a distance of a center block.
+    Circle {
+        /// The x and z block coordinate of the center.
+        center: (i32, i32),
+        /// The distance in blocks from the center.
+        radius: u32,
+    },
+    /// A whole region, by its region coordinate, e.g. -1,2 for r.-1.2.mca.
+    Region(i32, i32),
+}
+
+impl AreaShape {
+    /// Creates a rectangle from any two of its opposite corners.
+    pub fn rectangle(corner: (i32, i32), opposite: (i32, i32)) -> Self {
+        Self::Rectangle {
+            min: (corner.0.min(opposite.0), corner.1.min(opposite.1)),
+            max: (corner.0.max(opposite.0), corner.1.max(opposite.1)),
+        }
+    }
+
+    /// Returns true if the shape covers any block of the rectangle of blocks from min to max, both included.
+    fn overlaps(&self, min: (i64, i64), max: (i64, i64)) -> bool {
+        match *self {
+            Self::Rectangle {
+                min: shape_min,
+                max: shape_max,
+            } => {
+                shape_min.0 as i64 <= max.0
+                    && shape_max.0 as i64 >= min.0
+                    && shape_min.1 as i64 <= max.1
+                    && shape_max.1 as i64 >= min.1
+            }
+            Self::Circle { center, radius } => {
+                // the block of the rectangle closest to the center decides if any of it is within the radius.
+                let dx = (center.0 as i64).clamp(min.0, max.0) - center.0 as i64;
+                let dz = (center.1 as i64).clamp(min.1, max.1) - center.1 as i64;
+                dx * dx + dz * dz <= radius as i64 * radius as i64
+            }
+            Self::Region(x, z) => {
+                // region coordinates far out make block coordinates that do not fit in an i32.
+                let (region_min_x, region_min_z) = (x as i64 * 512, z as i64 * 512);
+                region_min_x <= max.0
+                    && region_min_x + 511 >= min.0
+                    && region_min_z <= max.1
+                    && region_min_z + 511 >= min.1
+            }
+        }
+    }
+}
+
+/// The part of a world a render is limited to, every block covered by any of its shapes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderArea {
+    /// The shapes making up the area.
+    pub shapes: Vec<AreaShape>,
+}
+
+impl RenderArea {
+    /// Creates an area covering every block covered by any of the shapes.
+    pub const fn new(shapes: Vec<AreaShape>) -> Self {
+        Self { shapes }
+    }
+
+    /// Returns true if any block of the rectangle from min to max, both included, is in the area.
+    fn overlaps(&self, min: (i64, i64), max: (i64, i64)) -> bool {
+        self.shapes.iter().any(|shape| shape.overlaps(min, max))
+    }
+
+    /// Returns true if any block of the region is in the area.
+    pub fn overlaps_region(&self, coordinate: &ChunkCoordinate) -> bool {
+        let min = (coordinate.0 as i64 * 512, coordinate.1 as i64 * 512);
+        self.overlaps(min, (min.0 + 511, min.1 + 511))
+    }
+
+    /// Returns true if any block of the chunk is in the area, given by its chunk coordinate, which is its block coordinate divided by 16.
+    pub fn overlaps_chunk(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        let min = (chunk_x as i64 * 16, chunk_z as i64 * 16);
+        self.overlaps(min, (min.0 + 15, min.1 + 15))
+    }
+
+    /// Returns whether each chunk of a region is in the area, indexed by z * 32 + x, or none if all of them are.
+    pub fn chunk_mask(&self, coordinate: &ChunkCoordinate) -> Option<Vec<bool>> {
+        let mask: Vec<bool> = (0..32 * 32)
+            .map(|index| {
+                let min = (
+                    (coordinate.0 as i64 * 32 + index % 32) * 16,
+                    (coordinate.1 as i64 * 32 + index / 32) * 16,
+                );
+                self.overlaps(min, (min.0 + 15, min.1 + 15))
+            })
+            .collect();
+        if mask.iter().all(|selected| *selected) {
+            None
+        } else {
+            Some(mask)
+        }
+    }
+
+    /// Limits a region file to the chunks of it in the area, returns none if the region is outside of the area.
+    pub fn select(&self, mut region: RegionFile) -> Option<RegionFile> {
+        if !self.overlaps_region(&region.coordinate) {
+            return None;
+        }
+        region.selected_chunks = self.chunk_mask(&region.coordinate);
+        Some(region)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn selected_chunks(mask: &[bool]) -> Vec<(i32, i32)> {
+        (0..32 * 32)
+            .filter(|index| mask[*index as usize])
+            .map(|index| (index % 32, index / 32))
+            .collect()
+    }
+
+    #[test]
+    fn whole_region_has_no_mask() {
+        let area = RenderArea::new(vec![AreaShape::Region(-1, 2)]);
+        assert!(area.overlaps_region(&ChunkCoordinate(-1, 2)));
+        assert_eq!(area.chunk_mask(&ChunkCoordinate(-1, 2)), None);
+        assert!(!area.overlaps_region(&ChunkCoordinate(0, 2)));
+        assert!(!area.overlaps_region(&ChunkCoordinate(-1, 1)));
+    }
+
+    #[test]
+    fn rectangle_masks_the_chunks_it_touches() {
+        // blocks -20..=5 and 0..=16 touch chunks -2..=0 along x and 0..=1 along z.
+        let area = RenderArea::new(vec![AreaShape::rectangle((5, 16), (-20, 0))]);
+        assert!(area.overlaps_region(&ChunkCoordinate(-1, 0)));
+        assert!(area.overlaps_region(&ChunkCoordinate(0, 0)));
+        assert!(!area.overlaps_region(&ChunkCoordinate(0, -1)));
+
+        let mask = area.chunk_mask(&ChunkCoordinate(-1, 0)).unwrap();
+        assert_eq!(
+            selected_chunks(&mask),
+            vec![(30, 0), (31, 0), (30, 1), (31, 1)]
+        );
+        let mask = area.chunk_mask(&ChunkCoordinate(0, 0)).unwrap();
+        assert_eq!(selected_chunks(&mask), vec![(0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn circle_masks_the_chunks_within_its_radius() {
+        let area = RenderArea::new(vec![AreaShape::Circle {
+            center: (8, 8),
+            radius: 30,
+        }]);
+        assert!(area.overlaps_region(&ChunkCoordinate(-1, -1)));
+        assert!(!area.overlaps_region(&ChunkCoordinate(1, 0)));
+
+        // chunk 2,2 is the only one of the 3x3 chunks from the center that is further than 30 blocks away from it.
+        let mask = area.chunk_mask(&ChunkCoordinate(0, 0)).unwrap();
+        assert_eq!(
+            selected_chunks(&mask),
+            vec![
+                (0, 0),
+                (1, 0),
+                (2, 0),
+                (0, 1),
+                (1, 1),
+                (2, 1),
+                (0, 2),
+                (1, 2)
+            ]
+        );
+        assert!(area.overlaps_chunk(-1, -1));
+        assert!(!area.overlaps_chunk(-3, 0));
+    }
+
+    #[test]
+    fn shapes_are_combined() {
+        let area = RenderArea::new(vec![
+            AreaShape::Region(0, 0),
+            AreaShape::rectangle((512, 0), (512, 0)),
+        ]);
+        assert_eq!(area.chunk_mask(&ChunkCoordinate(0, 0)), None);
+        let mask = area.chunk_mask(&ChunkCoordinate(1, 0)).unwrap();
+        assert_eq!(selected_chunks(&mask), vec![(0, 0)]);
+        assert!(!area.overlaps_region(&ChunkCoordinate(0, 1)));
+    }
+
+    #[test]
+    fn far_out_regions_do_not_overflow() {
+        let area = RenderArea::new(vec![AreaShape::Region(i32::MAX, i32::MIN)]);
+        assert!(area.overlaps_region(&ChunkCoordinate(i32::MAX, i32::MIN)));
+        assert!(!area.overlaps_region(&ChunkCoordinate(0, 0)));
+        assert_eq!(area.chunk_mask(&ChunkCoordinate(i32::MAX, i32::MIN)), None);
+    }
+}
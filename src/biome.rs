@@ -0,0 +1,265 @@
+//! Biome tinting for the blocks that minecraft colors at runtime, like grass, leaves and water.
+//!
+//! The textures for these blocks are stored as grayscale images and get multiplied by a color that depends on the biome they are in.
+
+use crate::{TextureListMap, COLOR_OVERRIDE_PREFIX};
+use fastanvil::biome::Biome;
+use image::{GenericImageView, Rgb};
+
+/// The key in the texture list that the grass colormap is stored under, if one was loaded.
+pub const GRASS_COLORMAP_KEY: &str = "colormap:grass";
+/// The key in the texture list that the foliage colormap is stored under, if one was loaded.
+pub const FOLIAGE_COLORMAP_KEY: &str = "colormap:foliage";
+
+/// The default water color minecraft uses for most biomes.
+const DEFAULT_WATER_COLOR: [u8; 3] = [0x3F, 0x76, 0xE4];
+
+/// Corner colors of the vanilla grass colormap triangle, ordered hot and wet, cold, hot and dry.
+/// Used when no grass colormap texture was provided.
+const GRASS_CORNERS: [[f32; 3]; 3] = [
+    [71.0, 205.0, 51.0],
+    [128.0, 180.0, 151.0],
+    [191.0, 183.0, 85.0],
+];
+/// Corner colors of the vanilla foliage colormap triangle, ordered the same way as the grass corners.
+const FOLIAGE_CORNERS: [[f32; 3]; 3] = [
+    [26.0, 191.0, 0.0],
+    [96.0, 161.0, 123.0],
+    [174.0, 164.0, 42.0],
+];
+
+/// The kind of runtime coloring a block receives.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TintType {
+    /// Colored using the grass colormap, e.g. grass blocks and ferns.
+    Grass,
+    /// Colored using the foliage colormap, e.g. oak leaves and vines.
+    Foliage,
+    /// Colored using the biome water color.
+    Water,
+    /// Colored using a color that does not depend on the biome, e.g. birch leaves.
+    Fixed([u8; 3]),
+}
+
+/// Returns the kind of tint a block should receive, or none if the texture of the block is already colored.
+pub fn tint_type_for_block(block_name: &str) -> Option<TintType> {
+    let name = block_name.strip_prefix("minecraft:").unwrap_or(block_name);
+    match name {
+        "grass_block" | "grass" | "short_grass" | "tall_grass" | "fern" | "large_fern"
+        | "potted_fern" | "sugar_cane" => Some(TintType::Grass),
+        "oak_leaves" | "jungle_leaves" | "acacia_leaves" | "dark_oak_leaves" | "vine" => {
+            Some(TintType::Foliage)
+        }
+        "water" | "bubble_column" => Some(TintType::Water),
+        // the game hardcodes these colors instead of using the colormaps.
+        "birch_leaves" => Some(TintType::Fixed([0x80, 0xA7, 0x55])),
+        "spruce_leaves" => Some(TintType::Fixed([0x61, 0x99, 0x61])),
+        "mangrove_leaves" => Some(TintType::Fixed([0x92, 0xC6, 0x48])),
+        "lily_pad" => Some(TintType::Fixed([0x20, 0x80, 0x30])),
+        _ => None,
+    }
+}
+
+/// Returns the color a block should be multiplied by, given the biome it was found in.
+/// Returns none if the block is not a tinted block, or its color is overridden, see `override_block_colors`.
+pub fn block_tint(
+    block_name: &str,
+    biome: Option<Biome>,
+    texture_list: &TextureListMap,
+) -> Option<Rgb<u8>> {
+    if texture_list.contains_key(&format!("{}{}", COLOR_OVERRIDE_PREFIX, block_name)) {
+        return None;
+    }
+    let color = match tint_type_for_block(block_name)? {
+        TintType::Grass => grass_color(biome, texture_list),
+        TintType::Foliage => foliage_color(biome, texture_list),
+        TintType::Water => water_color(biome),
+        TintType::Fixed(color) => color,
+    };
+    Some(Rgb(color))
+}
+
+/// Multiplies a texture pixel by a tint color, the same way minecraft does for tinted textures.
+pub const fn multiply_tint(color: Rgb<u8>, tint: Rgb<u8>) -> Rgb<u8> {
+    Rgb([
+        ((color.0[0] as u16 * tint.0[0] as u16) / 255) as u8,
+        ((color.0[1] as u16 * tint.0[1] as u16) / 255) as u8,
+        ((color.0[2] as u16 * tint.0[2] as u16) / 255) as u8,
+    ])
+}
+
+/// Returns the grass color for a biome, taking into account the biomes that override the colormap.
+pub fn grass_color(biome: Option<Biome>, texture_list: &TextureListMap) -> [u8; 3] {
+    use Biome::*;
+    match biome {
+        Some(Swamp | SwampHills | MangroveSwamp) => [0x6A, 0x70, 0x39],
+        Some(
+            Badlands
+            | BadlandsPlateau
+            | ErodedBadlands
+            | WoodedBadlands
+            | WoodedBadlandsPlateau
+            | ModifiedBadlandsPlateau
+            | ModifiedWoodedBadlandsPlateau,
+        ) => [0x90, 0x81, 0x4D],
+        Some(DarkForest | DarkForestHills) => {
+            // dark forests average the colormap color with a fixed dark green.
+            let base = colormap_color(biome, texture_list.get(GRASS_COLORMAP_KEY), &GRASS_CORNERS);
+            let dark = [0x28, 0x34, 0x0A];
+            [
+                (((base[0] & 0xFE) as u16 + dark[0]) >> 1) as u8,
+                (((base[1] & 0xFE) as u16 + dark[1]) >> 1) as u8,
+                (((base[2] & 0xFE) as u16 + dark[2]) >> 1) as u8,
+            ]
+        }
+        _ => colormap_color(biome, texture_list.get(GRASS_COLORMAP_KEY), &GRASS_CORNERS),
+    }
+}
+
+/// Returns the foliage color for a biome, taking into account the biomes that override the colormap.
+pub fn foliage_color(biome: Option<Biome>, texture_list: &TextureListMap) -> [u8; 3] {
+    use Biome::*;
+    match biome {
+        Some(Swamp | SwampHills) => [0x6A, 0x70, 0x39],
+        Some(MangroveSwamp) => [0x8D, 0xB1, 0x27],
+        Some(
+            Badlands
+            | BadlandsPlateau
+            | ErodedBadlands
+            | WoodedBadlands
+            | WoodedBadlandsPlateau
+            | ModifiedBadlandsPlateau
+            | ModifiedWoodedBadlandsPlateau,
+        ) => [0x9E, 0x81, 0x4D],
+        _ => colormap_color(
+            biome,
+            texture_list.get(FOLIAGE_COLORMAP_KEY),
+            &FOLIAGE_CORNERS,
+        ),
+    }
+}
+
+/// Returns the water color for a biome, water does not use a colormap and instead each biome has a set color.
+pub const fn water_color(biome: Option<Biome>) -> [u8; 3] {
+    use Biome::*;
+    match biome {
+        Some(Swamp | SwampHills) => [0x61, 0x7B, 0x64],
+        Some(MangroveSwamp) => [0x3A, 0x7A, 0x6A],
+        Some(WarmOcean | DeepWarmOcean) => [0x43, 0xD5, 0xEE],
+        Some(LukewarmOcean | DeepLukewarmOcean) => [0x45, 0xAD, 0xF2],
+        Some(ColdOcean | DeepColdOcean | SnowyBeach | SnowyTaiga | SnowyTaigaHills) => {
+            [0x3D, 0x57, 0xD6]
+        }
+        Some(FrozenOcean | DeepFrozenOcean | FrozenRiver) => [0x39, 0x38, 0xC9],
+        Some(Meadow) => [0x0E, 0x4E, 0xCF],
+        _ => DEFAULT_WATER_COLOR,
+    }
+}
+
+/// Looks up the color for a biome in a colormap, the same way minecraft does.
+/// If no colormap texture is given, the color is interpolated between the corners of the vanilla colormap triangle instead.
+fn colormap_color(
+    biome: Option<Biome>,
+    colormap: Option<&image::DynamicImage>,
+    corners: &[[f32; 3]; 3],
+) -> [u8; 3] {
+    // biomes we do not know about get the same colors as plains, which is what most worlds are made of anyway.
+    let (temperature, downfall) = match biome {
+        None | Some(Biome::Unknown) => (0.8, 0.4),
+        Some(b) => {
+            let climate = b.climate();
+            (climate.temperature as f32, climate.rainfall as f32)
+        }
+    };
+    let temperature = temperature.clamp(0.0, 1.0);
+    let downfall = downfall.clamp(0.0, 1.0) * temperature;
+
+    // position inside of the colormap, where 0,0 is the top left.
+    let x = 1.0 - temperature;
+    let y = 1.0 - downfall;
+
+    match colormap {
+        Some(map) if map.width() > 0 && map.height() > 0 => {
+            let px = ((x * (map.width() - 1) as f32) as u32).min(map.width() - 1);
+            let py = ((y * (map.height() - 1) as f32) as u32).min(map.height() - 1);
+            let pixel = map.get_pixel(px, py).0;
+            [pixel[0], pixel[1], pixel[2]]
+        }
+        _ => {
+            // barycentric weights of the hot and wet corner (0,0), the cold corner (1,1) and the hot and dry corner (0,1)
+            let weights = [1.0 - y, x, y - x];
+            let mut out = [0u8; 3];
+            for (channel, value) in out.iter_mut().enumerate() {
+                let mixed: f32 = weights
+                    .iter()
+                    .zip(corners.iter())
+                    .map(|(weight, corner)| weight * corner[channel])
+                    .sum();
+                *value = mixed.round().clamp(0.0, 255.0) as u8;
+            }
+            out
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    #[test]
+    fn tint_types_are_looked_up_by_block_name() {
+        assert_eq!(
+            tint_type_for_block("minecraft:grass_block"),
+            Some(TintType::Grass)
+        );
+        assert_eq!(tint_type_for_block("oak_leaves"), Some(TintType::Foliage));
+        assert_eq!(
+            tint_type_for_block("minecraft:water"),
+            Some(TintType::Water)
+        );
+        assert_eq!(
+            tint_type_for_block("minecraft:birch_leaves"),
+            Some(TintType::Fixed([0x80, 0xA7, 0x55]))
+        );
+        // the cauldron texture is already colored, only the water inside of it is tinted.
+        assert_eq!(tint_type_for_block("minecraft:water_cauldron"), None);
+        assert_eq!(tint_type_for_block("minecraft:stone"), None);
+    }
+
+    #[test]
+    fn colors_fall_back_to_the_corners_of_the_colormap_triangle() {
+        let textures = TextureListMap::new();
+        // deserts are the hottest and driest biome, and snowy plains the coldest.
+        assert_eq!(grass_color(Some(Biome::Desert), &textures), [191, 183, 85]);
+        assert_eq!(
+            foliage_color(Some(Biome::SnowyPlains), &textures),
+            [96, 161, 123]
+        );
+        assert_eq!(grass_color(Some(Biome::Plains), &textures), [140, 189, 87]);
+        assert_eq!(grass_color(None, &textures), [140, 189, 87]);
+        assert_eq!(
+            grass_color(Some(Biome::Swamp), &textures),
+            [0x6A, 0x70, 0x39]
+        );
+    }
+
+    #[test]
+    fn colors_are_read_from_the_colormap_when_there_is_one() {
+        let mut colormap = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+        colormap.put_pixel(0, 1, Rgba([10, 20, 30, 255]));
+        let mut textures = TextureListMap::new();
+        textures.insert(GRASS_COLORMAP_KEY.to_string(), DynamicImage::from(colormap));
+
+        assert_eq!(grass_color(Some(Biome::Desert), &textures), [10, 20, 30]);
+        assert_eq!(
+            block_tint("minecraft:grass_block", Some(Biome::Desert), &textures),
+            Some(Rgb([10, 20, 30]))
+        );
+        // foliage has no colormap loaded, so it still uses the triangle.
+        assert_eq!(
+            foliage_color(Some(Biome::Desert), &textures),
+            [174, 164, 42]
+        );
+    }
+}
@@ -0,0 +1,401 @@
+//! A render cache that lets a world be re-rendered incrementally, only drawing the chunks that changed since the last run.
+//!
+//! Every region file header stores the time each chunk was last saved, these are recorded in a manifest in the output directory
+//! along with a fingerprint of the textures and render options used. On the next run, chunks whose timestamp did not change
+//! are copied out of the previously saved region image instead of being rendered again.
+
+use crate::error::{McMapperError, Result};
+use crate::models::BlockModels;
+use crate::report::RenderReport;
+use crate::{ChunkCoordinate, RegionFile, RenderOptions, TextureListMap};
+use image::RgbImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// The name of the cache manifest inside of the output directory.
+pub const CACHE_FILE_NAME: &str = "render-cache.json";
+
+/// Number of chunks in a region.
+const CHUNKS_PER_REGION: usize = 32 * 32;
+/// The timestamp recorded for chunks that were not drawn, because they could not be read or are outside of the rendered area,
+/// which never matches a real one so they are always read again.
+const UNDRAWN_CHUNK_TIMESTAMP: u32 = u32::MAX;
+
+/// Last saved timestamps of every chunk in each region, indexed by z * 32 + x, zero if the chunk does not exist.
+pub type RegionTimestamps = HashMap<ChunkCoordinate, Vec<u32>>;
+
+/// The cache manifest saved alongside the rendered images.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RenderCache {
+    /// A hash of the textures and render options the cached images were made with, if it changes the whole cache is invalid.
+    pub fingerprint: String,
+    /// The chunk timestamps of each region at the time it was rendered, keyed by the region coordinate, e.g. "-1,2".
+    pub regions: HashMap<String, Vec<u32>>,
+}
+
+/// A previously rendered region image, along with which of its chunks need to be rendered again.
+pub struct CachedRegion {
+    /// The region image from the last run.
+    pub image: RgbImage,
+    /// Whether each chunk needs to be rendered again, indexed by z * 32 + x.
+    pub dirty: Vec<bool>,
+}
+
+impl RenderCache {
+    /// Loads the cache manifest from the output directory, an empty cache is returned if it is missing, unreadable,
+    /// or was made with a different fingerprint.
+    pub fn load(output_dir: &Path, fingerprint: &str) -> Self {
+        let cache: Self = fs::read_to_string(output_dir.join(CACHE_FILE_NAME))
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+
+        if cache.fingerprint == fingerprint {
+            cache
+        } else {
+            Self {
+                fingerprint: fingerprint.to_string(),
+                regions: HashMap::new(),
+            }
+        }
+    }
+
+    /// Creates a cache manifest for the regions that were just rendered.
+    pub fn new(fingerprint: &str, timestamps: &RegionTimestamps) -> Self {
+        Self {
+            fingerprint: fingerprint.to_string(),
+            regions: timestamps
+                .iter()
+                .map(|(coordinate, stamps)| (coordinate.to_string(), stamps.clone()))
+                .collect(),
+        }
+    }
+
+    /// Records the timestamps of the regions that were just rendered, regions outside of the rendered area keep their entries.
+    pub fn update(&mut self, timestamps: &RegionTimestamps) {
+        for (coordinate, stamps) in timestamps {
+            self.regions.insert(coordinate.to_string(), stamps.clone());
+        }
+    }
+
+    /// Forgets the timestamps of chunks that could not be read, so they are read again on the next run and stay in its report.
+    pub fn forget_failures(&mut self, report: &RenderReport) {
+        for region in &report.regions {
+            let coordinate = ChunkCoordinate(region.region.0, region.region.1);
+            if let Some(stamps) = self.regions.get_mut(&coordinate.to_string()) {
+                if region.region_error.is_some() {
+                    stamps.fill(UNDRAWN_CHUNK_TIMESTAMP);
+                }
+                for failure in &region.failures {
+                    let (x, z) = (
+                        failure.chunk.0.rem_euclid(32),
+                        failure.chunk.1.rem_euclid(32),
+                    );
+                    if let Some(stamp) = stamps.get_mut(z as usize * 32 + x as usize) {
+                        *stamp = UNDRAWN_CHUNK_TIMESTAMP;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Saves the cache manifest into the output directory.
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(CACHE_FILE_NAME);
+        let text = serde_json::to_string(self).map_err(|err| McMapperError::json(&path, err))?;
+        fs::write(&path, text).map_err(|err| McMapperError::io(&path, err))
+    }
+
+    /// Works out which chunks of a region need to be rendered again, or none if the region is not in the cache.
+    ///
+    /// A chunk is dirty if its own timestamp changed, or if the chunk north of it changed, since that changes its shading.
+    pub fn dirty_chunks(
+        &self,
+        coordinate: &ChunkCoordinate,
+        current: &RegionTimestamps,
+    ) -> Option<Vec<bool>> {
+        let previous = self.regions.get(&coordinate.to_string())?;
+        let now = current.get(coordinate)?;
+        if previous.len() != CHUNKS_PER_REGION || now.len() != CHUNKS_PER_REGION {
+            return None;
+        }
+
+        let changed: Vec<bool> = previous.iter().zip(now).map(|(a, b)| a != b).collect();
+
+        // the southern most row of the region north of this one shades the first row of this region.
+        let north = coordinate.north();
+        let north_changed: Vec<bool> =
+            match (self.regions.get(&north.to_string()), current.get(&north)) {
+                (Some(previous), Some(now))
+                    if previous.len() == CHUNKS_PER_REGION && now.len() == CHUNKS_PER_REGION =>
+                {
+                    (0..32)
+                        .map(|x| previous[31 * 32 + x] != now[31 * 32 + x])
+                        .collect()
+                }
+                (None, None) => vec![false; 32],
+                _ => vec![true; 32], // the region north of this one appeared or disappeared
+            };
+
+        Some(
+            (0..CHUNKS_PER_REGION)
+                .map(|index| {
+                    let (x, z) = (index % 32, index / 32);
+                    changed[index]
+                        || if z == 0 {
+                            north_changed[x]
+                        } else {
+                            changed[index - 32]
+                        }
+                })
+                .collect(),
+        )
+    }
+
+    /// Loads the previous image of a region if it is in the cache, along with which of its chunks need to be rendered again.
+    pub fn cached_region(
+        &self,
+        coordinate: &ChunkCoordinate,
+        current: &RegionTimestamps,
+        image_path: &Path,
+        options: &RenderOptions,
+    ) -> Option<CachedRegion> {
+        let dirty = self.dirty_chunks(coordinate, current)?;
+        let image = image::open(image_path).ok()?.to_rgb8();
+        let region_pixels = options.block_style.region_pixels();
+        if image.width() != region_pixels || image.height() != region_pixels {
+            return None;
+        }
+        Some(CachedRegion { image, dirty })
+    }
+}
+
+/// Reads the last saved timestamp of every chunk in a region file from its header.
+pub fn read_chunk_timestamps(mut file: &File) -> Vec<u32> {
+    let mut header = [0u8; CHUNKS_PER_REGION * 4];
+    let read = file
+        .seek(SeekFrom::Start((CHUNKS_PER_REGION * 4) as u64))
+        .and_then(|_| file.read_exact(&mut header));
+    if read.is_err() {
+        return vec![0; CHUNKS_PER_REGION]; // a region without a full header has no chunks we can trust
+    }
+    header
+        .chunks_exact(4)
+        .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// Reads the chunk timestamps of every region file given.
+///
+/// Chunks the region files do not select are given a timestamp that never matches, so they are drawn once the rendered area grows.
+pub fn read_all_chunk_timestamps(list: &[RegionFile]) -> RegionTimestamps {
+    list.iter()
+        .map(|region| {
+            let mut stamps = read_chunk_timestamps(&region.file);
+            for (index, stamp) in stamps.iter_mut().enumerate() {
+                if !region.is_chunk_selected(index % 32, index / 32) {
+                    *stamp = UNDRAWN_CHUNK_TIMESTAMP;
+                }
+            }
+            (region.coordinate, stamps)
+        })
+        .collect()
+}
+
+/// Creates a fingerprint of the textures, block models and render options, so that the cache is thrown away if any of them change.
+pub fn render_fingerprint(
+    texture_list: &TextureListMap,
+    models: &BlockModels,
+    options: &RenderOptions,
+) -> String {
+    fingerprint(texture_list, models, options, env!("CARGO_PKG_VERSION"))
+}
+
+/// Creates the fingerprint of a render made by the given version of the renderer, see `render_fingerprint`.
+fn fingerprint(
+    texture_list: &TextureListMap,
+    models: &BlockModels,
+    options: &RenderOptions,
+    version: &str,
+) -> String {
+    let mut hasher = Fnv1a::new();
+    let mut names: Vec<&String> = texture_list.keys().collect();
+    names.sort();
+    for name in names {
+        hasher.write_field(name.as_bytes());
+        hasher.write_field(texture_list[name].as_bytes());
+    }
+    hasher.write_field(models.fingerprint().as_bytes());
+    hasher.write_field(format!("{:?}", options).as_bytes());
+    hasher.write_field(version.as_bytes());
+    format!("{:016x}", hasher.0)
+}
+
+/// The 64 bit FNV-1a hash, the fingerprint is saved between runs so it needs a hash that is the same on every platform and rust release, which `DefaultHasher` is not.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    const fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    /// Hashes a field after its length, so the end of one field can not be mistaken for the start of the next.
+    fn write_field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, Rgba, RgbaImage};
+
+    fn dirty_list(dirty: &[bool]) -> Vec<(usize, usize)> {
+        (0..CHUNKS_PER_REGION)
+            .filter(|index| dirty[*index])
+            .map(|index| (index % 32, index / 32))
+            .collect()
+    }
+
+    fn timestamps(regions: &[(ChunkCoordinate, &Vec<u32>)]) -> RegionTimestamps {
+        regions
+            .iter()
+            .map(|(coordinate, stamps)| (*coordinate, (*stamps).clone()))
+            .collect()
+    }
+
+    #[test]
+    fn unchanged_region_is_clean() {
+        let region = ChunkCoordinate(0, 0);
+        let stamps = vec![100; CHUNKS_PER_REGION];
+        let current = timestamps(&[(region, &stamps)]);
+        let cache = RenderCache::new("fingerprint", &current);
+        let dirty = cache.dirty_chunks(&region, &current).unwrap();
+        assert!(dirty_list(&dirty).is_empty());
+    }
+
+    #[test]
+    fn changed_chunk_dirties_the_chunk_south_of_it() {
+        let region = ChunkCoordinate(-1, 2);
+        let mut stamps = vec![100; CHUNKS_PER_REGION];
+        let cache = RenderCache::new("fingerprint", &timestamps(&[(region, &stamps)]));
+
+        stamps[5 * 32 + 3] = 200;
+        stamps[31 * 32 + 31] = 200;
+        let dirty = cache
+            .dirty_chunks(&region, &timestamps(&[(region, &stamps)]))
+            .unwrap();
+        assert_eq!(dirty_list(&dirty), vec![(3, 5), (3, 6), (31, 31)]);
+    }
+
+    #[test]
+    fn changes_in_the_region_to_the_north_dirty_the_first_row() {
+        let region = ChunkCoordinate(0, 0);
+        let north = region.north();
+        let stamps = vec![100; CHUNKS_PER_REGION];
+        let mut north_stamps = vec![100; CHUNKS_PER_REGION];
+        let cache = RenderCache::new(
+            "fingerprint",
+            &timestamps(&[(region, &stamps), (north, &north_stamps)]),
+        );
+
+        north_stamps[31 * 32 + 7] = 200;
+        north_stamps[30 * 32 + 8] = 200;
+        let dirty = cache
+            .dirty_chunks(
+                &region,
+                &timestamps(&[(region, &stamps), (north, &north_stamps)]),
+            )
+            .unwrap();
+        assert_eq!(dirty_list(&dirty), vec![(7, 0)]);
+
+        // a region appearing to the north dirties the whole first row.
+        let cache = RenderCache::new("fingerprint", &timestamps(&[(region, &stamps)]));
+        let dirty = cache
+            .dirty_chunks(
+                &region,
+                &timestamps(&[(region, &stamps), (north, &north_stamps)]),
+            )
+            .unwrap();
+        assert_eq!(
+            dirty_list(&dirty),
+            (0..32).map(|x| (x, 0)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn regions_missing_from_the_cache_are_not_cached() {
+        let region = ChunkCoordinate(0, 0);
+        let stamps = vec![100; CHUNKS_PER_REGION];
+        let cache = RenderCache::new("fingerprint", &timestamps(&[(region, &stamps)]));
+        let current = timestamps(&[(ChunkCoordinate(1, 0), &stamps)]);
+        assert!(cache
+            .dirty_chunks(&ChunkCoordinate(1, 0), &current)
+            .is_none());
+        assert!(cache.dirty_chunks(&region, &current).is_none());
+    }
+
+    #[test]
+    fn rendering_another_area_keeps_the_cached_regions_outside_of_it() {
+        let first = ChunkCoordinate(0, 0);
+        let second = ChunkCoordinate(5, 0);
+        let stamps = vec![100; CHUNKS_PER_REGION];
+        let mut cache = RenderCache::new("fingerprint", &timestamps(&[(first, &stamps)]));
+
+        cache.update(&timestamps(&[(second, &stamps)]));
+
+        let current = timestamps(&[(first, &stamps), (second, &stamps)]);
+        for region in [first, second] {
+            let dirty = cache.dirty_chunks(&region, &current).unwrap();
+            assert!(dirty_list(&dirty).is_empty());
+        }
+    }
+
+    #[test]
+    fn fnv1a_matches_the_reference_hashes() {
+        for (input, expected) in [
+            ("", 0xcbf2_9ce4_8422_2325),
+            ("a", 0xaf63_dc4c_8601_ec8c),
+            ("foobar", 0x8594_4171_f739_67e8),
+        ] {
+            let mut hasher = Fnv1a::new();
+            hasher.write(input.as_bytes());
+            assert_eq!(hasher.0, expected);
+        }
+    }
+
+    #[test]
+    fn fingerprints_do_not_change_between_builds() {
+        let mut texture_list = TextureListMap::new();
+        texture_list.insert(
+            "minecraft:stone".to_string(),
+            DynamicImage::from(RgbaImage::from_pixel(1, 1, Rgba([125, 125, 125, 255]))),
+        );
+        let options = RenderOptions::default();
+        let stone = fingerprint(&texture_list, &BlockModels::default(), &options, "0.1.0");
+        assert_eq!(stone, "a375d405c4e667f9");
+        assert_ne!(
+            stone,
+            fingerprint(
+                &TextureListMap::new(),
+                &BlockModels::default(),
+                &options,
+                "0.1.0"
+            )
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! Chunks of every format since 1.2, decoded with the fastanvil chunk type matching the version they were saved in.
+//!
+//! A world that has been played since before 1.18 keeps chunks in their old format until they are loaded again,
+//! so a single region file can hold chunks of several formats.
+
+use crate::legacy::legacy_block;
+use crate::surface::is_air;
+use fastanvil::biome::Biome;
+use fastanvil::pre13::Pre13Section;
+use fastanvil::{pre13, pre18, Block, Chunk, CurrentJavaChunk, HeightMode, Region};
+use fastnbt::from_bytes;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::io::{ErrorKind, Read, Seek};
+use std::ops::Range;
+
+/// The data version chunks were moved out of their Level compound and given sections below y 0, 21w43a.
+const CURRENT_FORMAT_DATA_VERSION: i32 = 2844;
+/// The data version blocks were stored by name instead of by numeric id, 17w47a, the snapshot that started the flattening.
+const FLATTENING_DATA_VERSION: i32 = 1451;
+
+/// A chunk, in the format of the version it was saved in.
+#[derive(Debug)]
+pub enum WorldChunk {
+    /// A chunk saved in 1.18 or later.
+    Current(CurrentJavaChunk),
+    /// A chunk saved from 1.13 to 1.17.
+    Pre18(pre18::JavaChunk),
+    /// A chunk saved from 1.2 to 1.12, whose blocks are numeric ids that are mapped to modern blocks.
+    Pre13(pre13::JavaChunk),
+}
+
+/// The data version of a chunk, missing for chunks saved before 1.9.
+#[derive(Deserialize)]
+struct StoredDataVersion {
+    #[serde(rename = "DataVersion")]
+    data_version: Option<i32>,
+}
+
+/// Why a chunk could not be drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkProblem {
+    /// The chunk has not been generated yet, which is normal at the edges of a world.
+    NotGenerated,
+    /// The chunk could not be read from the region file, e.g. because the file is cut short or points outside of itself.
+    IoError,
+    /// The chunk data could not be decompressed.
+    CompressionError,
+    /// The chunk data is not valid nbt.
+    NbtError,
+    /// The chunk is valid nbt, but not laid out like a chunk of any version that can be read.
+    UnsupportedFormat,
+}
+
+/// A chunk that could not be drawn, along with why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkError {
+    /// What kind of problem the chunk has.
+    pub problem: ChunkProblem,
+    /// A description of the problem, empty for chunks that are not generated.
+    pub message: String,
+}
+
+impl ChunkError {
+    pub(crate) fn new(problem: ChunkProblem, message: impl ToString) -> Self {
+        Self {
+            problem,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl Display for ChunkError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.problem {
+            ChunkProblem::NotGenerated => write!(f, "the chunk has not been generated yet"),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for ChunkError {}
+
+/// Reads a chunk from a region file and decodes it, returning it along with its raw nbt data, which `ChunkSurface` reads heightmaps from.
+pub fn load_chunk<S: Read + Seek>(
+    region: &mut Region<S>,
+    chunk_x: usize,
+    chunk_z: usize,
+) -> Result<(WorldChunk, Vec<u8>), ChunkError> {
+    let data = match region.read_chunk(chunk_x, chunk_z) {
+        Ok(Some(data)) => data,
+        Ok(None) => return Err(ChunkError::new(ChunkProblem::NotGenerated, "")),
+        // the decompressors report corrupt data as io errors, which is told apart from failing to read the file by its kind.
+        Err(fastanvil::Error::IO(err))
+            if matches!(err.kind(), ErrorKind::InvalidData | ErrorKind::InvalidInput) =>
+        {
+            return Err(ChunkError::new(ChunkProblem::CompressionError, err))
+        }
+        Err(err @ fastanvil::Error::UnknownCompression(_)) => {
+            return Err(ChunkError::new(ChunkProblem::CompressionError, err))
+        }
+        Err(err) => return Err(ChunkError::new(ChunkProblem::IoError, err)),
+    };
+    let chunk = read_chunk(&data)?;
+    Ok((chunk, data))
+}
+
+/// Decodes a chunk, picking its format from the data version it was saved with.
+pub fn read_chunk(data: &[u8]) -> Result<WorldChunk, ChunkError> {
+    let data_version = from_bytes::<StoredDataVersion>(data)
+        .map_err(|err| ChunkError::new(ChunkProblem::NbtError, err))?
+        .data_version
+        .unwrap_or(0);
+    let unsupported = |err| ChunkError::new(ChunkProblem::UnsupportedFormat, err);
+    if data_version >= CURRENT_FORMAT_DATA_VERSION {
+        Ok(WorldChunk::Current(from_bytes(data).map_err(unsupported)?))
+    } else if data_version >= FLATTENING_DATA_VERSION {
+        Ok(WorldChunk::Pre18(from_bytes(data).map_err(unsupported)?))
+    } else {
+        Ok(WorldChunk::Pre13(from_bytes(data).map_err(unsupported)?))
+    }
+}
+
+impl WorldChunk {
+    /// Returns the versions of the game the format of this chunk was saved by.
+    pub const fn saved_in(&self) -> &'static str {
+        match self {
+            Self::Current(_) => "1.18 or later",
+            Self::Pre18(_) => "1.13 to 1.17",
+            Self::Pre13(_) => "1.2 to 1.12",
+        }
+    }
+
+    /// Returns whether the section holding the given y level has any block other than air in it, as far as can be told without reading every block.
+    pub fn section_filled(&self, y: isize) -> bool {
+        match self {
+            Self::Current(chunk) => chunk
+                .sections
+                .as_ref()
+                .and_then(|tower| tower.get_section_for_y(y))
+                .is_some_and(|section| !section.block_states.palette().iter().all(is_air)),
+            Self::Pre18(chunk) => chunk
+                .level
+                .sections
+                .as_ref()
+                .and_then(|tower| tower.get_section_for_y(y))
+                .is_some_and(|section| {
+                    section.block_states.is_some() && !section.palette.iter().all(is_air)
+                }),
+            Self::Pre13(chunk) => chunk
+                .level
+                .sections
+                .as_ref()
+                .and_then(|tower| tower.get_section_for_y(y))
+                .is_some_and(|section| section.blocks.iter().any(|id| *id != 0)),
+        }
+    }
+}
+
+/// Returns the block at a position inside of a pre 1.13 section, from its numeric id and data value.
+fn pre13_block(section: &Pre13Section, x: usize, section_y: usize, z: usize) -> &'static Block {
+    let index = (section_y << 8) | (z << 4) | x;
+    // each block has an 8 bit id, 4 more bits of id in the add array for modded blocks, and a 4 bit data value, two to a byte.
+    let nibble = |array: &[i8]| {
+        let byte = array.get(index / 2).copied().unwrap_or(0) as u8;
+        if index.is_multiple_of(2) {
+            byte & 0x0F
+        } else {
+            byte >> 4
+        }
+    };
+    let mut id = section.blocks.get(index).copied().unwrap_or(0) as u8 as u16;
+    if let Some(add) = &section.add {
+        id |= (nibble(add) as u16) << 8;
+    }
+    legacy_block(id, nibble(&section.data))
+}
+
+impl Chunk for WorldChunk {
+    fn status(&self) -> String {
+        match self {
+            Self::Current(chunk) => chunk.status(),
+            Self::Pre18(chunk) => chunk.status(),
+            Self::Pre13(chunk) => chunk.status(),
+        }
+    }
+
+    fn surface_height(&self, x: usize, z: usize, mode: HeightMode) -> isize {
+        match self {
+            Self::Current(chunk) => chunk.surface_height(x, z, mode),
+            Self::Pre18(chunk) => chunk.surface_height(x, z, mode),
+            Self::Pre13(chunk) => chunk.surface_height(x, z, mode),
+        }
+    }
+
+    fn biome(&self, x: usize, y: isize, z: usize) -> Option<Biome> {
+        match self {
+            Self::Current(chunk) => chunk.biome(x, y, z),
+            Self::Pre18(chunk) => chunk.biome(x, y, z),
+            Self::Pre13(chunk) => chunk.biome(x, y, z),
+        }
+    }
+
+    fn block(&self, x: usize, y: isize, z: usize) -> Option<&Block> {
+        match self {
+            Self::Current(chunk) => chunk.block(x, y, z),
+            Self::Pre18(chunk) => chunk.block(x, y, z),
+            // fastanvil keeps the old names of many blocks, like grass for grass blocks, so the blocks are looked up here instead.
+            Self::Pre13(chunk) => {
+                let section = chunk.level.sections.as_ref()?.get_section_for_y(y)?;
+                let section_y = (y - section.y as isize * 16) as usize;
+                Some(pre13_block(section, x, section_y, z))
+            }
+        }
+    }
+
+    fn y_range(&self) -> Range<isize> {
+        match self {
+            Self::Current(chunk) => chunk.y_range(),
+            Self::Pre18(chunk) => chunk.y_range(),
+            Self::Pre13(chunk) => chunk.y_range(),
+        }
+    }
+}
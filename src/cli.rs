@@ -0,0 +1,246 @@
+//! The command line interface of mcmapper-rs, parsed with clap.
+
+use clap::{Args, Parser, Subcommand};
+use mcmapper_rs::dimension::Dimension;
+use std::path::PathBuf;
+
+/// Two opposite corners of a rectangle of block coordinates, each an x and z.
+pub type BlockBounds = ((i32, i32), (i32, i32));
+
+/// Renders minecraft worlds to images.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Render a world to a single stitched image, and smaller copies of it.
+    Render(RenderCommand),
+    /// Render a world to a z/x/y tile pyramid, along with a viewer that can be opened in a browser.
+    Tiles(TilesCommand),
+    /// Count the regions and chunks of a world and the formats they are saved in, without rendering it.
+    Stats(StatsCommand),
+    /// Print the blocks of a single column of a world, from the top of the world down.
+    Inspect(InspectCommand),
+    /// Render every profile in a profile file in a single run, or only the ones named.
+    Profile(ProfileCommand),
+}
+
+/// Which world to read, and which of its dimensions.
+#[derive(Args, Debug)]
+pub struct WorldArgs {
+    /// The world folder, or the region folder inside of one.
+    pub world: PathBuf,
+
+    /// The dimension to read, e.g. overworld, nether, end, or the id of a datapack dimension like mypack:mining.
+    /// The world path must be the world folder when this is given.
+    #[arg(long, value_parser = parse_dimension)]
+    pub dimension: Option<Dimension>,
+}
+
+/// Everything that decides how a world is rendered, shared by the render and tiles commands.
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+    #[command(flatten)]
+    pub world: WorldArgs,
+
+    /// The folder images are saved in, other dimensions than the overworld are saved in a folder inside of it.
+    #[arg(long, short, default_value = "output")]
+    pub output: PathBuf,
+
+    /// The folder block textures are read from when no packs are given.
+    #[arg(long, default_value = "assets")]
+    pub assets: PathBuf,
+
+    /// A client jar, resource pack zip or folder to read textures from instead of the assets folder, can be given more than once.
+    /// Packs are given lowest priority first, the same as stacking packs in game.
+    #[arg(long = "pack", value_name = "PACK")]
+    pub packs: Vec<PathBuf>,
+
+    /// Draw a block as a solid color instead of its texture, e.g. minecraft:water=#3f76e4, can be given more than once.
+    #[arg(long = "color", value_name = "BLOCK=COLOR", value_parser = parse_color_override)]
+    pub colors: Vec<(String, [u8; 3])>,
+
+    /// Draw one pixel per block using the average color of each texture, instead of the whole texture.
+    #[arg(long)]
+    pub pixel: bool,
+
+    /// The width in pixels each block is drawn with, a power of two from 2 to 64, e.g. 32 for a 32x pack.
+    #[arg(long, default_value_t = mcmapper_rs::DEFAULT_TEXTURE_SIZE, value_parser = parse_texture_size)]
+    pub texture_size: u32,
+
+    /// Draw every block as a cube seen from the south east instead of a square seen from above.
+    #[arg(long)]
+    pub isometric: bool,
+
+    /// Draw the first block at or below this y level instead of the surface, for mapping mines and caves.
+    #[arg(long, allow_negative_numbers = true)]
+    pub max_y: Option<isize>,
+
+    /// With --max-y, only draw blocks that have air above them, so cave floors show up instead of solid rock.
+    #[arg(long, requires = "max_y")]
+    pub air_above_only: bool,
+
+    /// Start drawing under the roof of the dimension, which is always done for the nether.
+    #[arg(long)]
+    pub skip_ceiling: bool,
+
+    /// Draw water, ice and glass as solid blocks instead of blending them over the blocks under them.
+    #[arg(long)]
+    pub opaque_water: bool,
+
+    /// Fill chunks that could not be read with a bright color, so corruption stands out on the map.
+    #[arg(long)]
+    pub error_overlay: bool,
+
+    /// Mark the last known position of every player in the rendered dimension.
+    #[arg(long)]
+    pub players: bool,
+
+    /// Ignore the render cache and draw every chunk again.
+    #[arg(long)]
+    pub full_render: bool,
+
+    /// The number of regions rendered at once, by default one for each cpu core.
+    #[arg(long, short = 'j')]
+    pub threads: Option<usize>,
+
+    /// Only render the region at this region coordinate, e.g. -1,2 for r.-1.2.mca, can be given more than once.
+    #[arg(long = "region", value_name = "X,Z", value_parser = parse_xz, allow_hyphen_values = true)]
+    pub regions: Vec<(i32, i32)>,
+
+    /// Only render the chunks overlapping this rectangle of block coordinates, given as two opposite corners.
+    #[arg(long, value_name = "X1,Z1,X2,Z2", value_parser = parse_bounds, allow_hyphen_values = true)]
+    pub bounds: Option<BlockBounds>,
+
+    /// Only render the chunks within this many blocks of --center, or of the world spawn if no center is given.
+    #[arg(long, value_name = "BLOCKS")]
+    pub radius: Option<u32>,
+
+    /// The block coordinate --radius is measured from, e.g. -100,250.
+    #[arg(long, value_name = "X,Z", value_parser = parse_xz, allow_hyphen_values = true, requires = "radius")]
+    pub center: Option<(i32, i32)>,
+}
+
+#[derive(Args, Debug)]
+pub struct RenderCommand {
+    #[command(flatten)]
+    pub render: RenderArgs,
+
+    /// The name the stitched images are saved with, without an extension.
+    #[arg(long, default_value = "map")]
+    pub image_name: String,
+
+    /// How many times smaller than full size to save the stitched image, can be given more than once.
+    /// Defaults to full size and 8 times smaller, or only full size with --pixel.
+    #[arg(long = "scale", value_name = "SCALE", value_parser = clap::value_parser!(u32).range(1..))]
+    pub scales: Vec<u32>,
+}
+
+#[derive(Args, Debug)]
+pub struct TilesCommand {
+    #[command(flatten)]
+    pub render: RenderArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct StatsCommand {
+    #[command(flatten)]
+    pub world: WorldArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct InspectCommand {
+    #[command(flatten)]
+    pub world: WorldArgs,
+
+    /// The x block coordinate of the column.
+    #[arg(allow_negative_numbers = true)]
+    pub x: i32,
+
+    /// The z block coordinate of the column.
+    #[arg(allow_negative_numbers = true)]
+    pub z: i32,
+}
+
+#[derive(Args, Debug)]
+pub struct ProfileCommand {
+    /// The profiles to render, every profile in the file is rendered if none are given.
+    pub profiles: Vec<String>,
+
+    /// The profile file to read.
+    #[arg(long, default_value = "mcmapper.toml")]
+    pub config: PathBuf,
+}
+
+pub fn parse_dimension(id: &str) -> Result<Dimension, String> {
+    Dimension::from_id(id).ok_or_else(|| {
+        format!("unknown dimension \"{}\", use overworld, nether, end, or the id of a datapack dimension like mypack:mining", id)
+    })
+}
+
+pub fn parse_texture_size(size: &str) -> Result<u32, String> {
+    match size.parse::<u32>() {
+        Ok(size) => validate_texture_size(size),
+        Err(_) => Err("must be a power of two from 2 to 64".to_string()),
+    }
+}
+
+/// Checks that a texture size is a power of two from 2 to 64.
+pub fn validate_texture_size(size: u32) -> Result<u32, String> {
+    if size.is_power_of_two() && (2..=64).contains(&size) {
+        Ok(size)
+    } else {
+        Err("must be a power of two from 2 to 64".to_string())
+    }
+}
+
+/// Parses a color written as hex, e.g. "#3f76e4".
+pub fn parse_color(text: &str) -> Result<[u8; 3], String> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(format!(
+            "\"{}\" is not a color, write colors as hex like #3f76e4",
+            text
+        ));
+    }
+    let color = u32::from_str_radix(hex, 16).map_err(|err| err.to_string())?;
+    Ok([(color >> 16) as u8, (color >> 8) as u8, color as u8])
+}
+
+fn parse_color_override(text: &str) -> Result<(String, [u8; 3]), String> {
+    let (block, color) = text
+        .split_once('=')
+        .ok_or_else(|| "expected a block and a color, e.g. minecraft:water=#3f76e4".to_string())?;
+    Ok((block.to_string(), parse_color(color)?))
+}
+
+/// Parses a list of whole numbers separated by commas, e.g. "-1,2".
+fn parse_numbers(text: &str, count: usize) -> Result<Vec<i32>, String> {
+    let numbers = text
+        .split(',')
+        .map(|number| number.trim().parse::<i32>())
+        .collect::<Result<Vec<i32>, _>>()
+        .map_err(|_| format!("expected {} whole numbers separated by commas", count))?;
+    if numbers.len() != count {
+        return Err(format!(
+            "expected {} whole numbers separated by commas",
+            count
+        ));
+    }
+    Ok(numbers)
+}
+
+/// Parses an x and z coordinate, of a region or a block, e.g. "-1,2".
+fn parse_xz(text: &str) -> Result<(i32, i32), String> {
+    let numbers = parse_numbers(text, 2)?;
+    Ok((numbers[0], numbers[1]))
+}
+
+fn parse_bounds(text: &str) -> Result<BlockBounds, String> {
+    let numbers = parse_numbers(text, 4)?;
+    Ok(((numbers[0], numbers[1]), (numbers[2], numbers[3])))
+}
@@ -0,0 +1,320 @@
+//! Converting between the positions of blocks, chunks and regions, and the pixels of the map they are drawn at.
+//!
+//! A chunk is 16 by 16 blocks and a region is 32 by 32 chunks. Converting down to a larger unit rounds towards negative infinity,
+//! so block -1 is in chunk -1 and region -1, and block -513 is in region -2.
+
+use crate::{ChunkCoordinate, RenderOptions};
+use std::fmt::{Display, Formatter};
+
+/// The number of blocks along one side of a chunk.
+pub const CHUNK_WIDTH: i32 = 16;
+/// The number of chunks along one side of a region.
+pub const REGION_CHUNK_WIDTH: i32 = 32;
+/// The number of blocks along one side of a region.
+pub const REGION_WIDTH: i32 = CHUNK_WIDTH * REGION_CHUNK_WIDTH;
+
+/// The position of a block in the world.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BlockPos {
+    /// The x coordinate, which grows to the east.
+    pub x: i32,
+    /// The y coordinate, which grows upwards.
+    pub y: i32,
+    /// The z coordinate, which grows to the south.
+    pub z: i32,
+}
+
+/// The position of a chunk in the world, its block coordinate divided by 16.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ChunkPos {
+    /// The x coordinate of the chunk.
+    pub x: i32,
+    /// The z coordinate of the chunk.
+    pub z: i32,
+}
+
+/// The position of a region in the world, its chunk coordinate divided by 32, e.g. -1,2 for r.-1.2.mca.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct RegionPos {
+    /// The x coordinate of the region.
+    pub x: i32,
+    /// The z coordinate of the region.
+    pub z: i32,
+}
+
+/// A full resolution pixel of a map, relative to the pixel block 0,0 of the world is drawn at, see `RenderOptions::map_pixel`.
+///
+/// This is the same space region origins are in, see `stitch::StitchedImage` to convert it to and from the pixels of a stitched image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct PixelPos {
+    /// The x coordinate of the pixel, which grows to the right.
+    pub x: i64,
+    /// The y coordinate of the pixel, which grows downwards.
+    pub y: i64,
+}
+
+impl BlockPos {
+    /// Creates the position of the block at x, y and z.
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Returns the chunk the block is in.
+    pub const fn chunk(&self) -> ChunkPos {
+        ChunkPos::new(
+            self.x.div_euclid(CHUNK_WIDTH),
+            self.z.div_euclid(CHUNK_WIDTH),
+        )
+    }
+
+    /// Returns the region the block is in.
+    pub const fn region(&self) -> RegionPos {
+        RegionPos::new(
+            self.x.div_euclid(REGION_WIDTH),
+            self.z.div_euclid(REGION_WIDTH),
+        )
+    }
+
+    /// Returns the x and z of the block within its chunk, from 0 to 15.
+    pub const fn in_chunk(&self) -> (usize, usize) {
+        (
+            self.x.rem_euclid(CHUNK_WIDTH) as usize,
+            self.z.rem_euclid(CHUNK_WIDTH) as usize,
+        )
+    }
+
+    /// Returns the x and z of the block within its region, from 0 to 511.
+    pub const fn in_region(&self) -> (usize, usize) {
+        (
+            self.x.rem_euclid(REGION_WIDTH) as usize,
+            self.z.rem_euclid(REGION_WIDTH) as usize,
+        )
+    }
+
+    /// Returns the full resolution pixel the middle of the top face of the block is drawn at.
+    pub fn map_pixel(&self, options: &RenderOptions) -> PixelPos {
+        options
+            .map_pixel(
+                self.x as f64 + 0.5,
+                self.y as f64 + 1.0,
+                self.z as f64 + 0.5,
+            )
+            .into()
+    }
+}
+
+impl ChunkPos {
+    /// Creates the position of the chunk at x and z.
+    pub const fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    /// Returns the region the chunk is in.
+    pub const fn region(&self) -> RegionPos {
+        RegionPos::new(
+            self.x.div_euclid(REGION_CHUNK_WIDTH),
+            self.z.div_euclid(REGION_CHUNK_WIDTH),
+        )
+    }
+
+    /// Returns the x and z of the chunk within its region, from 0 to 31, as used to read it from its region file.
+    pub const fn in_region(&self) -> (usize, usize) {
+        (
+            self.x.rem_euclid(REGION_CHUNK_WIDTH) as usize,
+            self.z.rem_euclid(REGION_CHUNK_WIDTH) as usize,
+        )
+    }
+
+    /// Returns the block at an x and z within the chunk, from 0 to 15, and a y level.
+    pub const fn block(&self, x: usize, y: i32, z: usize) -> BlockPos {
+        BlockPos::new(
+            self.x * CHUNK_WIDTH + x as i32,
+            y,
+            self.z * CHUNK_WIDTH + z as i32,
+        )
+    }
+}
+
+impl RegionPos {
+    /// Creates the position of the region at x and z.
+    pub const fn new(x: i32, z: i32) -> Self {
+        Self { x, z }
+    }
+
+    /// Returns the chunk at an x and z within the region, from 0 to 31.
+    pub const fn chunk(&self, x: usize, z: usize) -> ChunkPos {
+        ChunkPos::new(
+            self.x * REGION_CHUNK_WIDTH + x as i32,
+            self.z * REGION_CHUNK_WIDTH + z as i32,
+        )
+    }
+
+    /// Returns the block at an x and z within the region, from 0 to 511, and a y level.
+    pub const fn block(&self, x: usize, y: i32, z: usize) -> BlockPos {
+        BlockPos::new(
+            self.x * REGION_WIDTH + x as i32,
+            y,
+            self.z * REGION_WIDTH + z as i32,
+        )
+    }
+
+    /// Returns the name of the file the region is saved in, e.g. r.-1.2.mca.
+    pub fn file_name(&self) -> String {
+        format!("r.{}.{}.mca", self.x, self.z)
+    }
+
+    /// Returns the full resolution pixel the top left of the image of the region is placed at, see `RenderOptions::region_origin`.
+    pub fn map_origin(&self, options: &RenderOptions) -> PixelPos {
+        options.region_origin(&(*self).into()).into()
+    }
+}
+
+impl PixelPos {
+    /// Creates the position of the pixel at x and y.
+    pub const fn new(x: i64, y: i64) -> Self {
+        Self { x, y }
+    }
+
+    /// Returns the block drawn at the pixel.
+    ///
+    /// A pixel of a map does not tell the height of the block drawn at it, so the y level has to be given, e.g. from the
+    /// surface heights or sea level. On top down maps it is only used as the y of the returned block, on isometric maps
+    /// a pixel shows a whole line of blocks from the top of the world down and it picks which of them is returned.
+    pub fn block(&self, y: i32, options: &RenderOptions) -> BlockPos {
+        // the middle of the pixel, so pixels on the edge between two blocks are not rounded into the wrong one.
+        let (x, z) =
+            options.map_position((self.x as f64 + 0.5, self.y as f64 + 0.5), y as f64 + 1.0);
+        BlockPos::new(x.floor() as i32, y, z.floor() as i32)
+    }
+}
+
+impl From<ChunkCoordinate> for RegionPos {
+    fn from(coordinate: ChunkCoordinate) -> Self {
+        Self::new(coordinate.0, coordinate.1)
+    }
+}
+
+impl From<RegionPos> for ChunkCoordinate {
+    fn from(region: RegionPos) -> Self {
+        Self(region.x, region.z)
+    }
+}
+
+impl From<(i64, i64)> for PixelPos {
+    fn from(pixel: (i64, i64)) -> Self {
+        Self::new(pixel.0, pixel.1)
+    }
+}
+
+impl From<PixelPos> for (i64, i64) {
+    fn from(pixel: PixelPos) -> Self {
+        (pixel.x, pixel.y)
+    }
+}
+
+impl Display for BlockPos {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.x, self.y, self.z)
+    }
+}
+
+impl Display for ChunkPos {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.x, self.z)
+    }
+}
+
+impl Display for RegionPos {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", self.x, self.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BlockStyle, Projection};
+
+    #[test]
+    fn negative_blocks_round_down() {
+        let block = BlockPos::new(-1, 64, -1);
+        assert_eq!(block.chunk(), ChunkPos::new(-1, -1));
+        assert_eq!(block.chunk().region(), RegionPos::new(-1, -1));
+        assert_eq!(block.region(), RegionPos::new(-1, -1));
+        assert_eq!(block.in_chunk(), (15, 15));
+        assert_eq!(block.in_region(), (511, 511));
+
+        let block = BlockPos::new(-513, 64, 513);
+        assert_eq!(block.chunk(), ChunkPos::new(-33, 32));
+        assert_eq!(block.chunk().region(), RegionPos::new(-2, 1));
+        assert_eq!(block.region(), RegionPos::new(-2, 1));
+        assert_eq!(block.chunk().in_region(), (31, 0));
+        assert_eq!(block.in_region(), (511, 1));
+    }
+
+    #[test]
+    fn positions_within_round_trip() {
+        let block = BlockPos::new(-513, 70, 1000);
+        let (x, z) = block.in_chunk();
+        assert_eq!(block.chunk().block(x, block.y, z), block);
+        let (x, z) = block.in_region();
+        assert_eq!(block.region().block(x, block.y, z), block);
+        let (x, z) = block.chunk().in_region();
+        assert_eq!(block.region().chunk(x, z), block.chunk());
+        assert_eq!(block.region().file_name(), "r.-2.1.mca");
+    }
+
+    fn assert_pixels_round_trip(options: &RenderOptions) {
+        for block in [
+            BlockPos::new(0, 64, 0),
+            BlockPos::new(-1, -64, -1),
+            BlockPos::new(-513, 70, 513),
+            BlockPos::new(1234, 319, -4321),
+        ] {
+            let pixel = block.map_pixel(options);
+            assert_eq!(
+                pixel.block(block.y, options),
+                block,
+                "{:?} {:?}",
+                options,
+                pixel
+            );
+        }
+    }
+
+    #[test]
+    fn top_down_pixels_round_trip() {
+        for block_style in [
+            BlockStyle::textured(),
+            BlockStyle::Textured { size: 32 },
+            BlockStyle::AverageColor,
+        ] {
+            assert_pixels_round_trip(&RenderOptions {
+                block_style,
+                ..Default::default()
+            });
+        }
+    }
+
+    #[test]
+    fn isometric_pixels_round_trip() {
+        for block_style in [BlockStyle::textured(), BlockStyle::Textured { size: 32 }] {
+            assert_pixels_round_trip(&RenderOptions {
+                block_style,
+                projection: Projection::Isometric,
+                ..Default::default()
+            });
+        }
+    }
+
+    #[test]
+    fn region_origin_is_its_first_block() {
+        let options = RenderOptions::default();
+        let region = RegionPos::new(-2, 1);
+        assert_eq!(region.map_origin(&options), PixelPos::new(-2 * 8192, 8192));
+        assert_eq!(
+            region.block(0, 0, 0).map_pixel(&options),
+            PixelPos::new(-2 * 8192 + 8, 8192 + 8)
+        );
+    }
+}
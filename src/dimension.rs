@@ -0,0 +1,87 @@
+//! The dimensions of a world, each of which keeps its region files in a different folder inside of the world folder.
+
+use serde::{Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// A dimension of a world, the vanilla ones or a custom one added by a datapack.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dimension {
+    /// The overworld, stored in <world>/region.
+    Overworld,
+    /// The nether, stored in <world>/DIM-1/region.
+    Nether,
+    /// The end, stored in <world>/DIM1/region.
+    End,
+    /// A datapack dimension, stored in <world>/dimensions/<namespace>/<name>/region.
+    Custom {
+        /// The namespace of the dimension, e.g. "mypack" in "mypack:mining".
+        namespace: String,
+        /// The name of the dimension, e.g. "mining" in "mypack:mining".
+        name: String,
+    },
+}
+
+impl Dimension {
+    /// Parses a dimension from its id, e.g. "minecraft:the_nether" or "nether", or a custom id like "mypack:mining".
+    ///
+    /// Returns none if the id has no namespace and is not one of the vanilla dimensions.
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "overworld" | "minecraft:overworld" => Some(Self::Overworld),
+            "nether" | "the_nether" | "minecraft:the_nether" => Some(Self::Nether),
+            "end" | "the_end" | "minecraft:the_end" => Some(Self::End),
+            _ => id.split_once(':').map(|(namespace, name)| Self::Custom {
+                namespace: namespace.to_string(),
+                name: name.to_string(),
+            }),
+        }
+    }
+
+    /// Returns the folder holding the region files of this dimension inside of a world folder.
+    pub fn region_dir(&self, world: &Path) -> PathBuf {
+        match self {
+            Self::Overworld => world.join("region"),
+            Self::Nether => world.join("DIM-1").join("region"),
+            Self::End => world.join("DIM1").join("region"),
+            Self::Custom { namespace, name } => world
+                .join("dimensions")
+                .join(namespace)
+                .join(name)
+                .join("region"),
+        }
+    }
+
+    /// Returns true if the dimension has a solid roof, which has to be skipped to see the terrain under it.
+    pub const fn has_ceiling(&self) -> bool {
+        matches!(self, Self::Nether)
+    }
+
+    /// The name of the folder renders of this dimension are saved in, inside of the output folder.
+    /// The overworld is saved straight into the output folder.
+    pub fn output_folder_name(&self) -> Option<String> {
+        match self {
+            Self::Overworld => None,
+            Self::Nether => Some("nether".to_string()),
+            Self::End => Some("end".to_string()),
+            Self::Custom { namespace, name } => Some(format!("{}_{}", namespace, name)),
+        }
+    }
+}
+
+impl Display for Dimension {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Overworld => write!(f, "minecraft:overworld"),
+            Self::Nether => write!(f, "minecraft:the_nether"),
+            Self::End => write!(f, "minecraft:the_end"),
+            Self::Custom { namespace, name } => write!(f, "{}:{}", namespace, name),
+        }
+    }
+}
+
+impl Serialize for Dimension {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self) // saved as its full id, e.g. "minecraft:the_nether"
+    }
+}
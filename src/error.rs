@@ -0,0 +1,209 @@
+//! The error type returned by the library, so that programs embedding it can decide what to do when something goes wrong.
+
+use crate::chunk::ChunkError;
+use image::ImageError;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Shorthand for a result with the library error type.
+pub type Result<T> = std::result::Result<T, McMapperError>;
+
+/// Everything that can go wrong while reading a world and rendering it.
+#[derive(Debug)]
+pub enum McMapperError {
+    /// A file or directory could not be read or written.
+    Io {
+        /// The file or directory that was being accessed.
+        path: PathBuf,
+        /// The underlying io error.
+        source: io::Error,
+    },
+    /// A file in the region directory is not named like a region file, e.g. r.0.-1.mca.
+    BadRegionFileName(String),
+    /// A region file could not be read.
+    Region {
+        /// The region file that was being read.
+        path: PathBuf,
+        /// The underlying fastanvil error.
+        source: fastanvil::Error,
+    },
+    /// A zip file, like a resource pack or the client jar, could not be read.
+    Archive {
+        /// The zip file that was being read.
+        path: PathBuf,
+        /// The underlying zip error.
+        source: zip::result::ZipError,
+    },
+    /// A single chunk of a region could not be read.
+    Chunk {
+        /// The x and z coordinate of the chunk in the world, which is its block coordinate divided by 16.
+        chunk: (i32, i32),
+        /// Why the chunk could not be read.
+        source: ChunkError,
+    },
+    /// Nbt data, like a chunk, could not be decoded.
+    Nbt(fastnbt::error::Error),
+    /// A texture the renderer needs is not in the texture list.
+    MissingTexture(String),
+    /// An image could not be opened or saved.
+    Image {
+        /// The image file that was being read or written.
+        path: PathBuf,
+        /// The underlying image error.
+        source: ImageError,
+    },
+    /// A png image could not be streamed to disk.
+    PngEncode {
+        /// The image file that was being written.
+        path: PathBuf,
+        /// The underlying png error.
+        source: png::EncodingError,
+    },
+    /// A png image could not be streamed from disk.
+    PngDecode {
+        /// The image file that was being read.
+        path: PathBuf,
+        /// The underlying png error.
+        source: png::DecodingError,
+    },
+    /// A json file, like the render report or the render cache, could not be written.
+    Json {
+        /// The json file that was being written.
+        path: PathBuf,
+        /// The underlying serde_json error.
+        source: serde_json::Error,
+    },
+    /// There are no region images to stitch together.
+    NoRegionImages,
+    /// The world path is not a folder.
+    NotAFolder(PathBuf),
+    /// None of the regions of the world are in the area being rendered.
+    NoRegionsInArea,
+    /// The thread pool a render was limited to could not be created.
+    ThreadPool(rayon::ThreadPoolBuildError),
+    /// The render was cancelled before it finished.
+    Cancelled,
+}
+
+impl McMapperError {
+    /// Shorthand for wrapping an io error along with the path it happened on.
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        Self::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// Shorthand for wrapping a json error along with the path it happened on.
+    pub fn json(path: &Path, source: serde_json::Error) -> Self {
+        Self::Json {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// Shorthand for wrapping an image error along with the path it happened on.
+    pub fn image(path: &Path, source: ImageError) -> Self {
+        Self::Image {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+}
+
+impl Display for McMapperError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io { path, source } => write!(
+                f,
+                "Unable to access {}, check that it exists and permissions are correct: {}",
+                path.display(),
+                source
+            ),
+            Self::BadRegionFileName(name) => write!(
+                f,
+                "Unable to find coordinate of region file \"{}\", make sure the region folder only contains region files. \
+                Issue could also occur because the program was pointed to a folder that is neither a world folder nor the region folder inside of one. \
+                E.g. run program like mcmapper-rs render <world name> or mcmapper-rs render <world name>/region",
+                name
+            ),
+            Self::Region { path, source } => {
+                write!(f, "Unable to read region file {}: {}", path.display(), source)
+            }
+            Self::Archive { path, source } => write!(
+                f,
+                "Unable to read {}, check that it is a valid resource pack zip or jar: {}",
+                path.display(),
+                source
+            ),
+            Self::Chunk { chunk, source } => {
+                write!(f, "Unable to read chunk {},{}: {}", chunk.0, chunk.1, source)
+            }
+            Self::Nbt(source) => write!(f, "Unable to decode nbt data: {}", source),
+            Self::MissingTexture(name) => write!(
+                f,
+                "Unable to find texture \"{}\", make sure its png file is present.",
+                name
+            ),
+            Self::Image { path, source } => write!(
+                f,
+                "Unable to read or save image {}, check that it is a valid png image: {}",
+                path.display(),
+                source
+            ),
+            Self::PngEncode { path, source } => {
+                write!(f, "Unable to write image {}: {}", path.display(), source)
+            }
+            Self::PngDecode { path, source } => {
+                write!(f, "Unable to read image {}: {}", path.display(), source)
+            }
+            Self::Json { path, source } => {
+                write!(f, "Unable to write json file {}: {}", path.display(), source)
+            }
+            Self::NoRegionImages => write!(f, "There are no region images to stitch together."),
+            Self::NotAFolder(path) => write!(
+                f,
+                "World path \"{}\" is not a folder, please provide the program with a path to a minecraft world.",
+                path.display()
+            ),
+            Self::NoRegionsInArea => write!(
+                f,
+                "None of the regions in the area being rendered exist in the world, check the regions picked with --region, --bounds or --radius."
+            ),
+            Self::ThreadPool(source) => {
+                write!(f, "Unable to start the threads to render with: {}", source)
+            }
+            Self::Cancelled => write!(f, "The render was cancelled."),
+        }
+    }
+}
+
+impl std::error::Error for McMapperError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io { source, .. } => Some(source),
+            Self::Region { source, .. } => Some(source),
+            Self::Archive { source, .. } => Some(source),
+            Self::Chunk { source, .. } => Some(source),
+            Self::Nbt(source) => Some(source),
+            Self::Image { source, .. } => Some(source),
+            Self::PngEncode { source, .. } => Some(source),
+            Self::PngDecode { source, .. } => Some(source),
+            Self::Json { source, .. } => Some(source),
+            Self::ThreadPool(source) => Some(source),
+            Self::BadRegionFileName(_)
+            | Self::MissingTexture(_)
+            | Self::NoRegionImages
+            | Self::NotAFolder(_)
+            | Self::NoRegionsInArea
+            | Self::Cancelled => None,
+        }
+    }
+}
+
+impl From<fastnbt::error::Error> for McMapperError {
+    fn from(err: fastnbt::error::Error) -> Self {
+        Self::Nbt(err)
+    }
+}
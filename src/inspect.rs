@@ -0,0 +1,144 @@
+//! Reading a world without rendering it, to count what is in it or to look at the blocks of a single column.
+
+use crate::chunk::{load_chunk, ChunkProblem, WorldChunk};
+use crate::coordinate::BlockPos;
+use crate::error::{McMapperError, Result};
+use crate::surface::is_air;
+use crate::RegionFile;
+use fastanvil::biome::Biome;
+use fastanvil::{Chunk, Region};
+use std::fs::File;
+use std::path::Path;
+
+/// The number of chunks saved in each format, and the chunks that could not be read.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChunkStats {
+    /// Chunks saved in 1.18 or later.
+    pub current: u32,
+    /// Chunks saved from 1.13 to 1.17.
+    pub pre18: u32,
+    /// Chunks saved from 1.2 to 1.12.
+    pub pre13: u32,
+    /// Chunks that exist but could not be read, rendering lists why in render-report.json.
+    pub unreadable: u32,
+    /// Region files that could not be read at all, e.g. because they are empty or cut short.
+    pub unreadable_regions: u32,
+}
+
+impl ChunkStats {
+    /// The number of chunks that have been generated, whether or not they could be read.
+    pub const fn generated(&self) -> u32 {
+        self.current + self.pre18 + self.pre13 + self.unreadable
+    }
+
+    /// Adds the counts of another part of the world to these.
+    pub const fn add(&mut self, other: &Self) {
+        self.current += other.current;
+        self.pre18 += other.pre18;
+        self.pre13 += other.pre13;
+        self.unreadable += other.unreadable;
+        self.unreadable_regions += other.unreadable_regions;
+    }
+}
+
+/// Reads every chunk of a region and counts them by the format they are saved in.
+///
+/// A region file that can not be read at all is counted in `unreadable_regions`, so one bad file does not hide the rest of the world.
+pub fn region_chunk_stats(region_selected: &RegionFile) -> ChunkStats {
+    let mut stats = ChunkStats::default();
+    let mut region = match Region::from_stream(&region_selected.file) {
+        Ok(region) => region,
+        Err(_) => {
+            stats.unreadable_regions = 1;
+            return stats;
+        }
+    };
+    for chunk_z in 0..32 {
+        for chunk_x in 0..32 {
+            match load_chunk(&mut region, chunk_x, chunk_z) {
+                Ok((WorldChunk::Current(_), _)) => stats.current += 1,
+                Ok((WorldChunk::Pre18(_), _)) => stats.pre18 += 1,
+                Ok((WorldChunk::Pre13(_), _)) => stats.pre13 += 1,
+                Err(err) if err.problem == ChunkProblem::NotGenerated => {}
+                Err(_) => stats.unreadable += 1,
+            }
+        }
+    }
+    stats
+}
+
+/// A run of the same block in a column, from its top y level down to its bottom y level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockRun {
+    /// The block and its properties, e.g. "minecraft:oak_log|axis=y".
+    pub block: String,
+    /// The highest y level of the run.
+    pub top: isize,
+    /// The lowest y level of the run.
+    pub bottom: isize,
+}
+
+/// The blocks of a single column of a world.
+#[derive(Debug, Clone)]
+pub struct Column {
+    /// The versions of the game the chunk holding the column was saved by, see `WorldChunk::saved_in`.
+    pub saved_in: &'static str,
+    /// How far the chunk has been generated, "full" once it is done.
+    pub status: String,
+    /// The biome at the top block of the column.
+    pub biome: Option<Biome>,
+    /// The blocks of the column from the highest block that is not air down to the bottom of the world.
+    pub runs: Vec<BlockRun>,
+}
+
+/// Reads the column of blocks at a block x and z from the region folder of a dimension.
+pub fn inspect_column(region_dir: &Path, x: i32, z: i32) -> Result<Column> {
+    let block = BlockPos::new(x, 0, z);
+    let path = region_dir.join(block.region().file_name());
+    let file = File::open(&path).map_err(|err| McMapperError::io(&path, err))?;
+    let mut region =
+        Region::from_stream(file).map_err(|source| McMapperError::Region { path, source })?;
+    let chunk_pos = block.chunk();
+    let (chunk_x, chunk_z) = chunk_pos.in_region();
+    let (chunk, _) =
+        load_chunk(&mut region, chunk_x, chunk_z).map_err(|source| McMapperError::Chunk {
+            chunk: (chunk_pos.x, chunk_pos.z),
+            source,
+        })?;
+
+    let (block_x, block_z) = block.in_chunk();
+    let mut runs: Vec<BlockRun> = vec![];
+    for y in chunk.y_range().rev() {
+        let block = chunk.block(block_x, y, block_z);
+        // the air above the highest block is skipped, air below it is kept so caves show up.
+        if runs.is_empty() && block.is_none_or(is_air) {
+            continue;
+        }
+        let name = block.map_or_else(
+            || "minecraft:air".to_string(),
+            |block| {
+                block
+                    .encoded_description()
+                    .trim_end_matches('|')
+                    .to_string()
+            },
+        );
+        match runs.last_mut() {
+            Some(run) if run.block == name => run.bottom = y,
+            _ => runs.push(BlockRun {
+                block: name,
+                top: y,
+                bottom: y,
+            }),
+        }
+    }
+
+    Ok(Column {
+        saved_in: chunk.saved_in(),
+        status: chunk.status(),
+        biome: runs
+            .first()
+            .and_then(|run| chunk.biome(block_x, run.top, block_z)),
+        runs,
+    })
+}
@@ -0,0 +1,418 @@
+//! Isometric rendering, where the world is viewed from the south east at an angle and each block is drawn as a cube.
+//!
+//! A block is drawn as a sprite twice as wide as its top face is tall, the top face is a diamond and the left and right faces are shaded darker than it.
+//! Moving east moves a block right and down on screen, moving south moves it left and down, and moving up moves it straight up.
+//! Region images overlap each other once projected, so they are placed by their origin and stitched with black pixels left out.
+
+use crate::biome::{block_tint, multiply_tint};
+use crate::chunk::{ChunkError, ChunkProblem};
+use crate::error::{McMapperError, Result};
+use crate::models::BlockModels;
+use crate::report::{RegionReport, ERROR_OVERLAY_COLOR};
+use crate::shading::REGION_BLOCK_WIDTH;
+use crate::surface::load_chunk_surface;
+use crate::{
+    rotate_texture_pixel, top_texture, BlockStyle, ChunkCoordinate, RegionFile, RenderOptions,
+    TextureListMap,
+};
+use fastanvil::Chunk;
+use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel, Rgb, RgbImage, Rgba};
+
+/// The lowest y level drawn, blocks below it are cut off.
+pub const ISOMETRIC_MIN_Y: isize = -64;
+/// One above the highest y level drawn, blocks above it are cut off.
+pub const ISOMETRIC_MAX_Y: isize = 320;
+
+/// The y level chunks that could not be read are drawn at when the error overlay is on, sea level.
+const ERROR_OVERLAY_Y: isize = 62;
+/// Brightness multiplier for the left face of a block, which faces south.
+const LEFT_FACE_SHADE: f32 = 0.8;
+/// Brightness multiplier for the right face of a block, which faces east.
+const RIGHT_FACE_SHADE: f32 = 0.6;
+
+/// Half of the width of a block sprite, which is also the height of its top face and of its side faces.
+///
+/// Textured blocks are drawn as wide as the texture size, single color blocks are drawn 4 pixels wide so their top faces still line up on whole pixels.
+pub const fn block_half_width(style: BlockStyle) -> u32 {
+    match style {
+        BlockStyle::Textured { size } => {
+            if size / 2 > 2 {
+                size / 2
+            } else {
+                2
+            }
+        }
+        BlockStyle::AverageColor => 2,
+    }
+}
+
+/// The width and height of an isometric region image, which has room for a whole region at every y level that is drawn.
+pub const fn region_image_size(style: BlockStyle) -> (u32, u32) {
+    let half_width = block_half_width(style);
+    let region = REGION_BLOCK_WIDTH as u32;
+    (
+        region * 2 * half_width,
+        region * half_width + (ISOMETRIC_MAX_Y - ISOMETRIC_MIN_Y) as u32 * half_width,
+    )
+}
+
+/// The position of the top left pixel of an isometric region image in the stitched map.
+pub const fn region_origin(coordinate: &ChunkCoordinate, style: BlockStyle) -> (i64, i64) {
+    let half_width = block_half_width(style) as i64;
+    let region = REGION_BLOCK_WIDTH as i64;
+    (
+        (coordinate.0 as i64 - coordinate.1 as i64 - 1) * region * half_width,
+        (coordinate.0 as i64 + coordinate.1 as i64) * region * half_width / 2,
+    )
+}
+
+/// The full resolution pixel a block position is drawn at in an isometric map, relative to the pixel block 0,0 of the world is drawn at.
+pub fn map_pixel(x: f64, y: f64, z: f64, style: BlockStyle) -> (i64, i64) {
+    let half_width = block_half_width(style) as f64;
+    (
+        ((x - z) * half_width).floor() as i64,
+        (((x + z) / 2.0 + ISOMETRIC_MAX_Y as f64 - y) * half_width).floor() as i64,
+    )
+}
+
+/// The x and z position in the world shown at a full resolution pixel of an isometric map, at a y level.
+///
+/// A pixel shows a whole line of blocks from the top of the world down, so the y level picks which one of them is returned.
+pub fn map_position(pixel: (f64, f64), y: f64, style: BlockStyle) -> (f64, f64) {
+    let half_width = block_half_width(style) as f64;
+    let x_minus_z = pixel.0 / half_width;
+    let x_plus_z = (pixel.1 / half_width - ISOMETRIC_MAX_Y as f64 + y) * 2.0;
+    ((x_plus_z + x_minus_z) / 2.0, (x_plus_z - x_minus_z) / 2.0)
+}
+
+/// The top block of a column, with its textures already looked up.
+struct IsometricColumn<'a> {
+    y: isize,
+    top: &'a DynamicImage,
+    rotation: u32,
+    top_tint: Option<Rgb<u8>>,
+    side: &'a DynamicImage,
+    side_tint: Option<Rgb<u8>>,
+}
+
+/// Converts a region file into an isometric image and returns it along with a report of the chunks that could not be read.
+///
+/// Chunks that can not be read are left out, or drawn as a flat layer of the error overlay color if the options ask for it.
+/// Every column is drawn as a stack of cubes from its top block down to the lower of the columns in front of it, so cliffs show their side faces.
+/// Side faces use the side texture of a block if there is one, e.g. grass_block_side, otherwise the texture named after the block, or the top texture.
+pub fn region_to_isometric_image(
+    region_selected: &RegionFile,
+    texture_list: &TextureListMap,
+    models: &BlockModels,
+    options: &RenderOptions,
+) -> Result<(RgbImage, RegionReport)> {
+    let mut report = RegionReport::new(&region_selected.coordinate);
+    // a region file that can not be read at all is reported, and the rest of the world is still drawn.
+    let mut region = match fastanvil::Region::from_stream(&region_selected.file) {
+        Ok(region) => Some(region),
+        Err(source) => {
+            report.region_failed(&McMapperError::Region {
+                path: region_selected.path.clone(),
+                source,
+            });
+            None
+        }
+    };
+    let error_texture =
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb(ERROR_OVERLAY_COLOR)));
+
+    // the whole region has to be read before drawing, since cubes of one chunk overlap the chunks behind it.
+    let mut columns: Vec<Option<IsometricColumn>> =
+        Vec::with_capacity(REGION_BLOCK_WIDTH * REGION_BLOCK_WIDTH);
+    columns.resize_with(REGION_BLOCK_WIDTH * REGION_BLOCK_WIDTH, || None);
+    for chunk_x in 0..32 {
+        for chunk_z in 0..32 {
+            if !region_selected.is_chunk_selected(chunk_x, chunk_z) {
+                continue; // chunks outside of the rendered area are left empty
+            }
+            let loaded = match region.as_mut() {
+                Some(region) => load_chunk_surface(region, chunk_x, chunk_z),
+                // chunks of a region file that could not be read are drawn like chunks that could not be read, but not counted.
+                None => Err(ChunkError::new(
+                    ChunkProblem::IoError,
+                    "region file could not be read",
+                )),
+            };
+            let (chunk, surface) = match loaded {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    if region.is_some() {
+                        report.failed(chunk_x, chunk_z, &err);
+                    }
+                    // chunks that exist but can not be read are drawn as a flat layer of the error color at sea level, if asked for.
+                    if options.error_overlay && err.problem != ChunkProblem::NotGenerated {
+                        for z in 0..16 {
+                            for x in 0..16 {
+                                columns
+                                    [(chunk_z * 16 + z) * REGION_BLOCK_WIDTH + chunk_x * 16 + x] =
+                                    Some(IsometricColumn {
+                                        y: ERROR_OVERLAY_Y,
+                                        top: &error_texture,
+                                        rotation: 0,
+                                        top_tint: None,
+                                        side: &error_texture,
+                                        side_tint: None,
+                                    });
+                            }
+                        }
+                    }
+                    continue; // missing chunks are left empty
+                }
+            };
+            report.rendered();
+            for z in 0..16 {
+                for x in 0..16 {
+                    let (mc_block, y) =
+                        match surface.column_block(&chunk, x, z, options.surface_mode) {
+                            None => continue,
+                            Some(found) => found,
+                        };
+                    let (top, rotation) = top_texture(texture_list, models, mc_block)?;
+                    let side = texture_list
+                        .get(&format!("{}_side", mc_block.name()))
+                        .or_else(|| texture_list.get(mc_block.name()))
+                        .unwrap_or(top);
+                    let tint = block_tint(mc_block.name(), chunk.biome(x, y, z), texture_list);
+                    let region_x = chunk_x * 16 + x;
+                    let region_z = chunk_z * 16 + z;
+                    columns[region_z * REGION_BLOCK_WIDTH + region_x] = Some(IsometricColumn {
+                        y,
+                        top,
+                        rotation,
+                        top_tint: tint,
+                        // separate side textures like grass_block_side are already colored
+                        side_tint: if std::ptr::eq(side, top) { tint } else { None },
+                        side,
+                    });
+                }
+            }
+        }
+    }
+
+    let half_width = block_half_width(options.block_style) as i64;
+    let (width, height) = region_image_size(options.block_style);
+    let mut img: RgbImage = ImageBuffer::new(width, height);
+    let height_at = |x: usize, z: usize| -> Option<isize> {
+        if x >= REGION_BLOCK_WIDTH || z >= REGION_BLOCK_WIDTH {
+            return None;
+        }
+        columns[z * REGION_BLOCK_WIDTH + x]
+            .as_ref()
+            .map(|column| column.y)
+    };
+
+    // columns are drawn back to front, one diagonal at a time, so the columns in front are drawn over the ones behind them.
+    for diagonal in 0..(REGION_BLOCK_WIDTH * 2 - 1) {
+        let first_x = diagonal.saturating_sub(REGION_BLOCK_WIDTH - 1);
+        let last_x = diagonal.min(REGION_BLOCK_WIDTH - 1);
+        for x in first_x..=last_x {
+            let z = diagonal - x;
+            let column = match &columns[z * REGION_BLOCK_WIDTH + x] {
+                None => continue,
+                Some(column) => column,
+            };
+            // the cubes under the top block are only seen where the column south or east of it is lower, a missing neighbour shows the column down to the bottom.
+            let bottom = match (height_at(x, z + 1), height_at(x + 1, z)) {
+                (Some(south), Some(east)) => south.min(east) + 1,
+                _ => ISOMETRIC_MIN_Y,
+            }
+            .max(ISOMETRIC_MIN_Y)
+            .min(column.y);
+
+            let center_x = (x as i64 - z as i64 + REGION_BLOCK_WIDTH as i64) * half_width;
+            for y in bottom..=column.y.min(ISOMETRIC_MAX_Y - 1) {
+                let top_y =
+                    (x + z) as i64 * half_width / 2 + (ISOMETRIC_MAX_Y - 1 - y) as i64 * half_width;
+                draw_side_faces(&mut img, column, center_x, top_y, half_width);
+            }
+            if column.y < ISOMETRIC_MAX_Y {
+                let top_y = (x + z) as i64 * half_width / 2
+                    + (ISOMETRIC_MAX_Y - 1 - column.y) as i64 * half_width;
+                draw_top_face(&mut img, column, center_x, top_y, half_width);
+            }
+        }
+    }
+
+    Ok((img, report))
+}
+
+/// Draws the diamond shaped top face of a block, the top corner of the diamond is at the given pixel.
+fn draw_top_face(
+    img: &mut RgbImage,
+    column: &IsometricColumn,
+    center_x: i64,
+    top_y: i64,
+    half_width: i64,
+) {
+    let (texture_width, texture_height) = column.top.dimensions();
+    let inverse_rotation = (360 - column.rotation % 360) % 360;
+    for dy in 0..half_width {
+        for dx in -half_width..half_width {
+            // position inside of the block, 0 to 1 from west to east and from north to south
+            let across = (dx as f32 + 0.5) / half_width as f32;
+            let down = (dy as f32 + 0.5) / (half_width as f32 / 2.0);
+            let east = (down + across) / 2.0;
+            let south = (down - across) / 2.0;
+            if !(0.0..1.0).contains(&east) || !(0.0..1.0).contains(&south) {
+                continue; // outside of the diamond
+            }
+            // the top down renderer turns the texture clockwise, so turning the position the other way finds the texture pixel drawn there
+            let (texture_x, texture_y) = rotate_texture_pixel(
+                (east * texture_width as f32) as u32,
+                (south * texture_height as f32) as u32,
+                texture_width,
+                texture_height,
+                inverse_rotation,
+            );
+            let color = texture_color(column.top, texture_x, texture_y, column.top_tint, 1.0);
+            put_pixel(img, center_x + dx, top_y + dy, color);
+        }
+    }
+}
+
+/// Draws the south and east faces of a block, the top corner of the top face of the block is at the given pixel.
+fn draw_side_faces(
+    img: &mut RgbImage,
+    column: &IsometricColumn,
+    center_x: i64,
+    top_y: i64,
+    half_width: i64,
+) {
+    let (texture_width, texture_height) = column.side.dimensions();
+    for i in 0..half_width {
+        let texture_x = (i * texture_width as i64 / half_width) as u32;
+        // the left face runs from the west corner down to the south corner, the right face from the south corner up to the east corner
+        let left_top = top_y + half_width / 2 + i / 2;
+        let right_top = top_y + half_width - (i + 1) / 2;
+        for j in 0..half_width {
+            let texture_y = (j * texture_height as i64 / half_width) as u32;
+            let left = texture_color(
+                column.side,
+                texture_x,
+                texture_y,
+                column.side_tint,
+                LEFT_FACE_SHADE,
+            );
+            put_pixel(img, center_x - half_width + i, left_top + j, left);
+            let right = texture_color(
+                column.side,
+                texture_x,
+                texture_y,
+                column.side_tint,
+                RIGHT_FACE_SHADE,
+            );
+            put_pixel(img, center_x + i, right_top + j, right);
+        }
+    }
+}
+
+/// Returns the color of a texture pixel once it is tinted and shaded, along with its alpha.
+fn texture_color(
+    texture: &DynamicImage,
+    x: u32,
+    y: u32,
+    tint: Option<Rgb<u8>>,
+    shade: f32,
+) -> Rgba<u8> {
+    let pixel = texture.get_pixel(x.min(texture.width() - 1), y.min(texture.height() - 1));
+    let tinted = match tint {
+        None => pixel.to_rgb(),
+        Some(tint) => multiply_tint(pixel.to_rgb(), tint),
+    };
+    let shaded = tinted.map(|channel| (channel as f32 * shade) as u8);
+    Rgba([shaded.0[0], shaded.0[1], shaded.0[2], pixel.0[3]])
+}
+
+/// Puts a pixel into the image, pixels outside of the image and see-through pixels are left out, so the holes in textures like leaves show what was drawn behind them.
+///
+/// Pixels that are only partly see-through are drawn solid, the same as water and glass. Pure black is turned into the darkest gray, since black pixels are treated as empty when region images are stitched together.
+fn put_pixel(img: &mut RgbImage, x: i64, y: i64, color: Rgba<u8>) {
+    if color.0[3] == 0 || x < 0 || y < 0 || x >= img.width() as i64 || y >= img.height() as i64 {
+        return;
+    }
+    let color = color.to_rgb();
+    if color.0 == [0, 0, 0] {
+        img.put_pixel(x as u32, y as u32, Rgb([1, 1, 1]));
+    } else {
+        img.put_pixel(x as u32, y as u32, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::RgbaImage;
+
+    #[test]
+    fn neighbouring_regions_line_up() {
+        for style in [BlockStyle::textured(), BlockStyle::AverageColor] {
+            let half_width = block_half_width(style) as i64;
+            let region = REGION_BLOCK_WIDTH as i64;
+            let origin = region_origin(&ChunkCoordinate(0, 0), style);
+            // a region further east starts where its first column would be drawn if it were part of the region before it.
+            let east = region_origin(&ChunkCoordinate(1, 0), style);
+            assert_eq!(
+                (east.0 - origin.0, east.1 - origin.1),
+                (region * half_width, region * half_width / 2)
+            );
+            let south = region_origin(&ChunkCoordinate(0, 1), style);
+            assert_eq!(
+                (south.0 - origin.0, south.1 - origin.1),
+                (-region * half_width, region * half_width / 2)
+            );
+        }
+    }
+
+    #[test]
+    fn region_images_fit_every_y_level() {
+        assert_eq!(block_half_width(BlockStyle::textured()), 8);
+        assert_eq!(
+            region_image_size(BlockStyle::textured()),
+            (512 * 16, 512 * 8 + 384 * 8)
+        );
+        assert_eq!(
+            region_image_size(BlockStyle::AverageColor),
+            (512 * 4, 512 * 2 + 384 * 2)
+        );
+    }
+
+    #[test]
+    fn texture_colors_keep_their_alpha() {
+        let texture = DynamicImage::from(RgbaImage::from_pixel(2, 2, Rgba([200, 100, 0, 255])));
+        assert_eq!(
+            texture_color(&texture, 0, 0, None, LEFT_FACE_SHADE),
+            Rgba([160, 80, 0, 255])
+        );
+        // pixels past the edge of the texture are clamped to it.
+        assert_eq!(
+            texture_color(&texture, 5, 5, Some(Rgb([0, 0, 0])), 1.0),
+            Rgba([0, 0, 0, 255])
+        );
+        let leaves = DynamicImage::from(RgbaImage::from_pixel(2, 2, Rgba([50, 120, 30, 0])));
+        assert_eq!(texture_color(&leaves, 0, 0, None, 1.0).0[3], 0);
+    }
+
+    #[test]
+    fn pixels_outside_of_the_image_are_left_out() {
+        let mut img = RgbImage::new(2, 2);
+        put_pixel(&mut img, -1, 0, Rgba([9, 9, 9, 255]));
+        put_pixel(&mut img, 0, 2, Rgba([9, 9, 9, 255]));
+        assert!(img.pixels().all(|pixel| pixel.0 == [0, 0, 0]));
+        put_pixel(&mut img, 1, 1, Rgba([9, 9, 9, 255]));
+        assert_eq!(img.get_pixel(1, 1), &Rgb([9, 9, 9]));
+    }
+
+    #[test]
+    fn see_through_pixels_are_left_out_and_black_is_kept_apart_from_empty() {
+        let mut img = RgbImage::from_pixel(2, 1, Rgb([40, 40, 40]));
+        put_pixel(&mut img, 0, 0, Rgba([9, 9, 9, 0]));
+        assert_eq!(img.get_pixel(0, 0), &Rgb([40, 40, 40]));
+        put_pixel(&mut img, 1, 0, Rgba([0, 0, 0, 128]));
+        assert_eq!(img.get_pixel(1, 0), &Rgb([1, 1, 1]));
+    }
+}
@@ -0,0 +1,576 @@
+//! Blocks of chunks saved before 1.13, which were stored as a numeric id and a data value instead of a name and blockstate properties.
+//!
+//! Each id and data value is mapped to the block the 1.13 upgrade turns it into, named as it is today,
+//! along with the properties needed to pick its texture. Ids above 255 are only used by mods and are drawn as air.
+
+use fastanvil::Block;
+use serde_json::{json, Map, Value};
+use std::sync::OnceLock;
+
+/// The colors of wool, stained glass, terracotta and the like, in the order of their data values.
+const COLORS: [&str; 16] = [
+    "white",
+    "orange",
+    "magenta",
+    "light_blue",
+    "yellow",
+    "lime",
+    "pink",
+    "gray",
+    "light_gray",
+    "cyan",
+    "purple",
+    "blue",
+    "brown",
+    "green",
+    "red",
+    "black",
+];
+
+/// The kinds of wood, in the order of the data values of planks, saplings and wooden slabs.
+const WOODS: [&str; 8] = [
+    "oak", "spruce", "birch", "jungle", "acacia", "dark_oak", "oak", "oak",
+];
+
+/// The modern name of every block id, for blocks whose data value does not change which block they are.
+/// Blocks whose data value picks between several blocks are named in `modern_block`.
+const NAMES: [&str; 256] = [
+    "air",
+    "stone",
+    "grass_block",
+    "dirt",
+    "cobblestone",
+    "oak_planks",
+    "oak_sapling",
+    "bedrock",
+    "water",
+    "water",
+    "lava",
+    "lava",
+    "sand",
+    "gravel",
+    "gold_ore",
+    "iron_ore",
+    "coal_ore",
+    "oak_log",
+    "oak_leaves",
+    "sponge",
+    "glass",
+    "lapis_ore",
+    "lapis_block",
+    "dispenser",
+    "sandstone",
+    "note_block",
+    "red_bed",
+    "powered_rail",
+    "detector_rail",
+    "sticky_piston",
+    "cobweb",
+    "grass",
+    "dead_bush",
+    "piston",
+    "piston_head",
+    "white_wool",
+    "moving_piston",
+    "dandelion",
+    "poppy",
+    "brown_mushroom",
+    "red_mushroom",
+    "gold_block",
+    "iron_block",
+    "smooth_stone_slab",
+    "smooth_stone_slab",
+    "bricks",
+    "tnt",
+    "bookshelf",
+    "mossy_cobblestone",
+    "obsidian",
+    "torch",
+    "fire",
+    "spawner",
+    "oak_stairs",
+    "chest",
+    "redstone_wire",
+    "diamond_ore",
+    "diamond_block",
+    "crafting_table",
+    "wheat",
+    "farmland",
+    "furnace",
+    "furnace",
+    "oak_sign",
+    "oak_door",
+    "ladder",
+    "rail",
+    "cobblestone_stairs",
+    "oak_wall_sign",
+    "lever",
+    "stone_pressure_plate",
+    "iron_door",
+    "oak_pressure_plate",
+    "redstone_ore",
+    "redstone_ore",
+    "redstone_torch",
+    "redstone_torch",
+    "stone_button",
+    "snow",
+    "ice",
+    "snow_block",
+    "cactus",
+    "clay",
+    "sugar_cane",
+    "jukebox",
+    "oak_fence",
+    "carved_pumpkin",
+    "netherrack",
+    "soul_sand",
+    "glowstone",
+    "nether_portal",
+    "jack_o_lantern",
+    "cake",
+    "repeater",
+    "repeater",
+    "white_stained_glass",
+    "oak_trapdoor",
+    "infested_stone",
+    "stone_bricks",
+    "brown_mushroom_block",
+    "red_mushroom_block",
+    "iron_bars",
+    "glass_pane",
+    "melon",
+    "pumpkin_stem",
+    "melon_stem",
+    "vine",
+    "oak_fence_gate",
+    "brick_stairs",
+    "stone_brick_stairs",
+    "mycelium",
+    "lily_pad",
+    "nether_bricks",
+    "nether_brick_fence",
+    "nether_brick_stairs",
+    "nether_wart",
+    "enchanting_table",
+    "brewing_stand",
+    "cauldron",
+    "end_portal",
+    "end_portal_frame",
+    "end_stone",
+    "dragon_egg",
+    "redstone_lamp",
+    "redstone_lamp",
+    "oak_slab",
+    "oak_slab",
+    "cocoa",
+    "sandstone_stairs",
+    "emerald_ore",
+    "ender_chest",
+    "tripwire_hook",
+    "tripwire",
+    "emerald_block",
+    "spruce_stairs",
+    "birch_stairs",
+    "jungle_stairs",
+    "command_block",
+    "beacon",
+    "cobblestone_wall",
+    "flower_pot",
+    "carrots",
+    "potatoes",
+    "oak_button",
+    "skeleton_skull",
+    "anvil",
+    "trapped_chest",
+    "light_weighted_pressure_plate",
+    "heavy_weighted_pressure_plate",
+    "comparator",
+    "comparator",
+    "daylight_detector",
+    "redstone_block",
+    "nether_quartz_ore",
+    "hopper",
+    "quartz_block",
+    "quartz_stairs",
+    "activator_rail",
+    "dropper",
+    "white_terracotta",
+    "white_stained_glass_pane",
+    "acacia_leaves",
+    "acacia_log",
+    "acacia_stairs",
+    "dark_oak_stairs",
+    "slime_block",
+    "barrier",
+    "iron_trapdoor",
+    "prismarine",
+    "sea_lantern",
+    "hay_block",
+    "white_carpet",
+    "terracotta",
+    "coal_block",
+    "packed_ice",
+    "sunflower",
+    "white_banner",
+    "white_wall_banner",
+    "daylight_detector",
+    "red_sandstone",
+    "red_sandstone_stairs",
+    "red_sandstone_slab",
+    "red_sandstone_slab",
+    "spruce_fence_gate",
+    "birch_fence_gate",
+    "jungle_fence_gate",
+    "dark_oak_fence_gate",
+    "acacia_fence_gate",
+    "spruce_fence",
+    "birch_fence",
+    "jungle_fence",
+    "dark_oak_fence",
+    "acacia_fence",
+    "spruce_door",
+    "birch_door",
+    "jungle_door",
+    "acacia_door",
+    "dark_oak_door",
+    "end_rod",
+    "chorus_plant",
+    "chorus_flower",
+    "purpur_block",
+    "purpur_pillar",
+    "purpur_stairs",
+    "purpur_slab",
+    "purpur_slab",
+    "end_stone_bricks",
+    "beetroots",
+    "dirt_path",
+    "end_gateway",
+    "repeating_command_block",
+    "chain_command_block",
+    "frosted_ice",
+    "magma_block",
+    "nether_wart_block",
+    "red_nether_bricks",
+    "bone_block",
+    "structure_void",
+    "observer",
+    "white_shulker_box",
+    "orange_shulker_box",
+    "magenta_shulker_box",
+    "light_blue_shulker_box",
+    "yellow_shulker_box",
+    "lime_shulker_box",
+    "pink_shulker_box",
+    "gray_shulker_box",
+    "light_gray_shulker_box",
+    "cyan_shulker_box",
+    "purple_shulker_box",
+    "blue_shulker_box",
+    "brown_shulker_box",
+    "green_shulker_box",
+    "red_shulker_box",
+    "black_shulker_box",
+    "white_glazed_terracotta",
+    "orange_glazed_terracotta",
+    "magenta_glazed_terracotta",
+    "light_blue_glazed_terracotta",
+    "yellow_glazed_terracotta",
+    "lime_glazed_terracotta",
+    "pink_glazed_terracotta",
+    "gray_glazed_terracotta",
+    "light_gray_glazed_terracotta",
+    "cyan_glazed_terracotta",
+    "purple_glazed_terracotta",
+    "blue_glazed_terracotta",
+    "brown_glazed_terracotta",
+    "green_glazed_terracotta",
+    "red_glazed_terracotta",
+    "black_glazed_terracotta",
+    "white_concrete",
+    "white_concrete_powder",
+    "air",
+    "air",
+    "structure_block",
+];
+
+/// Every block id and data value mapped to its modern block, indexed by id * 16 + data value, built the first time it is needed.
+static LEGACY_BLOCKS: OnceLock<Vec<Block>> = OnceLock::new();
+
+/// Returns the modern block for a pre 1.13 block id and data value.
+pub fn legacy_block(id: u16, data: u8) -> &'static Block {
+    let blocks = LEGACY_BLOCKS.get_or_init(|| {
+        (0..256 * 16)
+            .map(|index| {
+                let (name, properties) = modern_block((index >> 4) as u8, (index & 15) as u8);
+                to_block(&name, &properties)
+            })
+            .collect()
+    });
+    if id > 255 {
+        return &blocks[0]; // a modded block, which there is no texture for anyway
+    }
+    &blocks[((id as usize) << 4) | (data & 15) as usize]
+}
+
+/// Builds a block the same way fastanvil reads one from a chunk palette, so its encoded description matches.
+fn to_block(name: &str, properties: &[(&str, String)]) -> Block {
+    let properties: Map<String, Value> = properties
+        .iter()
+        .map(|(key, value)| (key.to_string(), Value::String(value.clone())))
+        .collect();
+    serde_json::from_value(json!({
+        "Name": format!("minecraft:{}", name),
+        "Properties": properties,
+    }))
+    .expect("a block is always built from a name and string properties")
+}
+
+/// Returns the axis of a log or pillar from its data value, none for logs with bark on every side.
+const fn axis(data: u8) -> Option<&'static str> {
+    match data >> 2 & 3 {
+        0 => Some("y"),
+        1 => Some("x"),
+        2 => Some("z"),
+        _ => None,
+    }
+}
+
+/// Returns the half of a block a slab sits in from its data value.
+fn slab_type(data: u8) -> (&'static str, String) {
+    let half = if data & 8 == 0 { "bottom" } else { "top" };
+    ("type", half.to_string())
+}
+
+/// Returns the modern name and blockstate properties of a pre 1.13 block.
+fn modern_block(id: u8, data: u8) -> (String, Vec<(&'static str, String)>) {
+    let color = COLORS[data as usize & 15];
+    let wood = WOODS[data as usize & 7];
+    let named = |name: &str| (name.to_string(), vec![]);
+    let log = |name: String| match axis(data) {
+        Some(axis) => pillar(&(name + "_log"), axis),
+        None => pillar(&(name + "_wood"), "y"),
+    };
+    let double_slab = |name: &str| (name.to_string(), vec![("type", "double".to_string())]);
+
+    match id {
+        1 => named(
+            [
+                "stone",
+                "granite",
+                "polished_granite",
+                "diorite",
+                "polished_diorite",
+                "andesite",
+                "polished_andesite",
+            ]
+            .get(data as usize)
+            .unwrap_or(&"stone"),
+        ),
+        3 => named(
+            ["dirt", "coarse_dirt", "podzol"]
+                .get(data as usize)
+                .unwrap_or(&"dirt"),
+        ),
+        5 => named(&format!("{}_planks", wood)),
+        6 => named(&format!("{}_sapling", wood)),
+        12 if data == 1 => named("red_sand"),
+        17 => log(["oak", "spruce", "birch", "jungle"][data as usize & 3].to_string()),
+        18 => named(&format!(
+            "{}_leaves",
+            ["oak", "spruce", "birch", "jungle"][data as usize & 3]
+        )),
+        19 if data == 1 => named("wet_sponge"),
+        24 => named(
+            ["sandstone", "chiseled_sandstone", "cut_sandstone"]
+                .get(data as usize)
+                .unwrap_or(&"sandstone"),
+        ),
+        31 => named(
+            ["dead_bush", "grass", "fern"]
+                .get(data as usize)
+                .unwrap_or(&"grass"),
+        ),
+        35 => named(&format!("{}_wool", color)),
+        38 => named(
+            [
+                "poppy",
+                "blue_orchid",
+                "allium",
+                "azure_bluet",
+                "red_tulip",
+                "orange_tulip",
+                "white_tulip",
+                "pink_tulip",
+                "oxeye_daisy",
+            ]
+            .get(data as usize)
+            .unwrap_or(&"poppy"),
+        ),
+        43 => match data {
+            8 => named("smooth_stone"),
+            9 => named("smooth_sandstone"),
+            15 => named("smooth_quartz"),
+            _ => double_slab(stone_slab(data)),
+        },
+        44 => (stone_slab(data).to_string(), vec![slab_type(data)]),
+        50 if (1..=4).contains(&data) => named("wall_torch"),
+        62 | 74 | 124 => (
+            NAMES[id as usize].to_string(),
+            vec![("lit", "true".to_string())],
+        ),
+        75 => (
+            NAMES[id as usize].to_string(),
+            vec![("lit", "false".to_string())],
+        ),
+        78 => (
+            "snow".to_string(),
+            vec![("layers", (data % 8 + 1).to_string())],
+        ),
+        95 => named(&format!("{}_stained_glass", color)),
+        97 => named(
+            [
+                "infested_stone",
+                "infested_cobblestone",
+                "infested_stone_bricks",
+                "infested_mossy_stone_bricks",
+                "infested_cracked_stone_bricks",
+                "infested_chiseled_stone_bricks",
+            ]
+            .get(data as usize)
+            .unwrap_or(&"infested_stone"),
+        ),
+        98 => named(
+            [
+                "stone_bricks",
+                "mossy_stone_bricks",
+                "cracked_stone_bricks",
+                "chiseled_stone_bricks",
+            ]
+            .get(data as usize)
+            .unwrap_or(&"stone_bricks"),
+        ),
+        99 | 100 if data == 10 || data == 15 => named("mushroom_stem"),
+        125 => double_slab(&format!("{}_slab", wood)),
+        126 => (format!("{}_slab", wood), vec![slab_type(data)]),
+        139 if data == 1 => named("mossy_cobblestone_wall"),
+        145 => named(["anvil", "chipped_anvil", "damaged_anvil", "anvil"][data as usize >> 2 & 3]),
+        155 => match data {
+            1 => named("chiseled_quartz_block"),
+            2 => pillar("quartz_pillar", "y"),
+            3 => pillar("quartz_pillar", "x"),
+            4 => pillar("quartz_pillar", "z"),
+            _ => named("quartz_block"),
+        },
+        159 => named(&format!("{}_terracotta", color)),
+        160 => named(&format!("{}_stained_glass_pane", color)),
+        161 => named(["acacia_leaves", "dark_oak_leaves"][data as usize & 1]),
+        162 => log(["acacia", "dark_oak"][data as usize & 1].to_string()),
+        168 => named(
+            ["prismarine", "prismarine_bricks", "dark_prismarine"]
+                .get(data as usize)
+                .unwrap_or(&"prismarine"),
+        ),
+        170 | 202 | 216 => pillar(NAMES[id as usize], axis(data).unwrap_or("y")),
+        171 => named(&format!("{}_carpet", color)),
+        // the upper half of a double plant does not store which plant it is, tall grass is by far the most common one.
+        175 if data & 8 != 0 => (
+            "tall_grass".to_string(),
+            vec![("half", "upper".to_string())],
+        ),
+        175 => (
+            [
+                "sunflower",
+                "lilac",
+                "tall_grass",
+                "large_fern",
+                "rose_bush",
+                "peony",
+                "tall_grass",
+                "tall_grass",
+            ][data as usize & 7]
+                .to_string(),
+            vec![("half", "lower".to_string())],
+        ),
+        178 => (
+            "daylight_detector".to_string(),
+            vec![("inverted", "true".to_string())],
+        ),
+        179 => named(
+            [
+                "red_sandstone",
+                "chiseled_red_sandstone",
+                "cut_red_sandstone",
+            ]
+            .get(data as usize)
+            .unwrap_or(&"red_sandstone"),
+        ),
+        181 if data & 8 != 0 => named("smooth_red_sandstone"),
+        181 | 204 => double_slab(NAMES[id as usize]),
+        182 | 205 => (NAMES[id as usize].to_string(), vec![slab_type(data)]),
+        251 => named(&format!("{}_concrete", color)),
+        252 => named(&format!("{}_concrete_powder", color)),
+        _ => named(NAMES[id as usize]),
+    }
+}
+
+/// Returns the name of a stone slab from its data value, which is shared by single and double slabs.
+const fn stone_slab(data: u8) -> &'static str {
+    [
+        "smooth_stone_slab",
+        "sandstone_slab",
+        "petrified_oak_slab",
+        "cobblestone_slab",
+        "brick_slab",
+        "stone_brick_slab",
+        "nether_brick_slab",
+        "quartz_slab",
+    ][data as usize & 7]
+}
+
+/// A log or pillar block standing along the given axis.
+fn pillar(name: &str, axis: &str) -> (String, Vec<(&'static str, String)>) {
+    (name.to_string(), vec![("axis", axis.to_string())])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoded(id: u16, data: u8) -> &'static str {
+        legacy_block(id, data).encoded_description()
+    }
+
+    #[test]
+    fn plain_ids_are_named() {
+        assert_eq!(legacy_block(0, 0).name(), "minecraft:air");
+        assert_eq!(legacy_block(2, 0).name(), "minecraft:grass_block");
+        assert_eq!(legacy_block(4, 0).name(), "minecraft:cobblestone");
+        // modded ids past 255 have no texture and are drawn as air.
+        assert_eq!(legacy_block(1000, 0).name(), "minecraft:air");
+    }
+
+    #[test]
+    fn data_values_pick_the_variant() {
+        assert_eq!(legacy_block(1, 3).name(), "minecraft:diorite");
+        assert_eq!(legacy_block(1, 12).name(), "minecraft:stone");
+        assert_eq!(legacy_block(5, 2).name(), "minecraft:birch_planks");
+        assert_eq!(legacy_block(35, 14).name(), "minecraft:red_wool");
+        assert_eq!(legacy_block(159, 0).name(), "minecraft:white_terracotta");
+        assert_eq!(legacy_block(161, 1).name(), "minecraft:dark_oak_leaves");
+    }
+
+    #[test]
+    fn properties_match_modern_blocks() {
+        assert_eq!(encoded(17, 0), "minecraft:oak_log|axis=y");
+        assert_eq!(encoded(17, 10), "minecraft:birch_log|axis=z");
+        assert_eq!(encoded(17, 13), "minecraft:spruce_wood|axis=y");
+        assert_eq!(encoded(162, 5), "minecraft:dark_oak_log|axis=x");
+        assert_eq!(encoded(44, 11), "minecraft:cobblestone_slab|type=top");
+        assert_eq!(encoded(43, 4), "minecraft:brick_slab|type=double");
+        assert_eq!(encoded(43, 8), "minecraft:smooth_stone|");
+        assert_eq!(encoded(78, 2), "minecraft:snow|layers=3");
+        assert_eq!(encoded(155, 3), "minecraft:quartz_pillar|axis=x");
+        assert_eq!(encoded(175, 4), "minecraft:rose_bush|half=lower");
+        assert_eq!(encoded(175, 10), "minecraft:tall_grass|half=upper");
+    }
+}
@@ -5,24 +5,183 @@
 //! This lib file is an extraction of the functions needed for the program to work.
 //! They were moved here for readability sake.
 
-use fastanvil::{Block, Chunk, CurrentJavaChunk};
-use fastnbt::from_bytes;
-use image::imageops::FilterType;
-use image::{imageops, DynamicImage, GenericImageView, ImageBuffer, Pixel, RgbImage};
+use crate::animation::first_frame;
+use crate::area::RenderArea;
+use crate::biome::{block_tint, multiply_tint, FOLIAGE_COLORMAP_KEY, GRASS_COLORMAP_KEY};
+use crate::cache::CachedRegion;
+use crate::chunk::{ChunkProblem, WorldChunk};
+use crate::error::{McMapperError, Result};
+use crate::models::BlockModels;
+use crate::report::{RegionReport, ERROR_OVERLAY_COLOR};
+use crate::resources::{block_texture_name, ResourcePack};
+use crate::shading::{
+    apply_hillshading_to_chunks, blend, translucent_opacity, RegionEdgeHeights, RegionHeights,
+    REGION_BLOCK_WIDTH,
+};
+use crate::surface::{is_translucent, load_chunk_surface, ChunkSurface, SurfaceMode};
+use fastanvil::{Block, Chunk};
+use image::{DynamicImage, GenericImageView, ImageBuffer, Pixel, Rgb, RgbImage, Rgba, RgbaImage};
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::{fs};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub mod animation;
+pub mod area;
+pub mod biome;
+pub mod cache;
+pub mod chunk;
+pub mod coordinate;
+pub mod dimension;
+pub mod error;
+pub mod inspect;
+pub mod isometric;
+pub mod legacy;
+pub mod models;
+pub mod players;
+pub mod renderer;
+pub mod report;
+pub mod resources;
+pub mod shading;
+pub mod stitch;
+pub mod surface;
+pub mod tiles;
+pub mod world;
+
+/// The number of pixels along one side of a textured block unless another size is picked, the size of the vanilla textures.
+pub const DEFAULT_TEXTURE_SIZE: u32 = 16;
+
+/// The texture list key prefix of block color overrides, e.g. "color:minecraft:water", see `override_block_colors`.
+pub const COLOR_OVERRIDE_PREFIX: &str = "color:";
+
+/// The texture drawn for any block without a texture, built into the program so it does not depend on the folder it is run from.
+const ERROR_TEXTURE: &[u8] = include_bytes!("../error.png");
+
+/// How each block is drawn into the output image.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BlockStyle {
+    /// Every block is drawn as its full texture, scaled to the given size, a region image is 512 times the size wide, e.g. 8192x8192 pixels for 16x16 textures.
+    Textured {
+        /// The number of pixels along one side of a block, e.g. 32 to keep the detail of a 32x resource pack.
+        size: u32,
+    },
+    /// Every block is drawn as a single pixel of the average color of its texture, a region image is 512x512 pixels.
+    /// The texture list must be converted using `average_texture_list` for this style.
+    AverageColor,
+}
+
+impl BlockStyle {
+    /// The number of pixels along one side of a block drawn in this style.
+    pub const fn block_pixels(&self) -> u32 {
+        match self {
+            Self::Textured { size } => *size,
+            Self::AverageColor => 1,
+        }
+    }
+
+    /// Textured blocks drawn at the size of the vanilla textures.
+    pub const fn textured() -> Self {
+        Self::Textured {
+            size: DEFAULT_TEXTURE_SIZE,
+        }
+    }
+
+    /// The number of pixels along one side of a chunk drawn in this style.
+    pub const fn chunk_pixels(&self) -> u32 {
+        self.block_pixels() * 16
+    }
+
+    /// The number of pixels along one side of a region drawn in this style.
+    pub const fn region_pixels(&self) -> u32 {
+        self.chunk_pixels() * 32
+    }
+}
+
+/// The direction the world is viewed from when it is drawn.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Projection {
+    /// Straight down from above, each block is drawn as a square.
+    #[default]
+    TopDown,
+    /// From above at an angle, each block is drawn as a cube showing its top, left and right faces, see the isometric module.
+    Isometric,
+}
+
+impl Default for BlockStyle {
+    fn default() -> Self {
+        Self::textured()
+    }
+}
+
+/// Options that change how regions and chunks are rendered.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct RenderOptions {
+    /// How each block is drawn into the output image.
+    pub block_style: BlockStyle,
+    /// Which block of each column is drawn, the highest one or the first one under the roof of the world.
+    pub surface_mode: SurfaceMode,
+    /// The direction the world is viewed from.
+    pub projection: Projection,
+    /// Draw water, ice and glass as solid blocks, instead of blending them over the blocks under them by how deep they are.
+    /// Isometric renders always draw them as solid blocks.
+    pub opaque_water: bool,
+    /// Fill chunks that exist but could not be read with `report::ERROR_OVERLAY_COLOR` instead of leaving them black.
+    pub error_overlay: bool,
+}
+
+impl RenderOptions {
+    /// The position of the top left pixel of a region image in the stitched map, relative to the pixel block 0,0 of the world is drawn at.
+    pub const fn region_origin(&self, coordinate: &ChunkCoordinate) -> (i64, i64) {
+        match self.projection {
+            Projection::TopDown => {
+                let region_pixels = self.block_style.region_pixels() as i64;
+                (
+                    coordinate.0 as i64 * region_pixels,
+                    coordinate.1 as i64 * region_pixels,
+                )
+            }
+            Projection::Isometric => isometric::region_origin(coordinate, self.block_style),
+        }
+    }
+
+    /// The full resolution pixel a block position is drawn at, relative to the pixel block 0,0 of the world is drawn at.
+    ///
+    /// This is the same space region origins are in, see `stitch::StitchedImage` to find the pixel in a stitched image.
+    pub fn map_pixel(&self, x: f64, y: f64, z: f64) -> (i64, i64) {
+        match self.projection {
+            Projection::TopDown => {
+                let block_pixels = self.block_style.block_pixels() as f64;
+                ((x * block_pixels).floor() as i64, (z * block_pixels).floor() as i64)
+            }
+            Projection::Isometric => isometric::map_pixel(x, y, z, self.block_style),
+        }
+    }
+
+    /// The x and z position in the world drawn at a full resolution pixel, the reverse of `map_pixel` for a known y position.
+    ///
+    /// Top down maps draw every y position at the same pixel, so the y position only matters for isometric maps.
+    pub fn map_position(&self, pixel: (f64, f64), y: f64) -> (f64, f64) {
+        match self.projection {
+            Projection::TopDown => {
+                let block_pixels = self.block_style.block_pixels() as f64;
+                (pixel.0 / block_pixels, pixel.1 / block_pixels)
+            }
+            Projection::Isometric => isometric::map_position(pixel, y, self.block_style),
+        }
+    }
+}
 
 /// This struct represents the minimum bounding box that can be drawn around an image that would lose no data from cropping to said bounding box.
 pub struct BoundingBox(pub (u32, u32), pub (u32, u32));
 
-/// This function is a
+/// This function is a shorthand for the file name a region image should be saved with.
 pub fn region_file_to_file_name(region: &RegionFile) -> String {
     format!("r.{}.{}.png", region.coordinate.0, region.coordinate.1)
 }
 
 /// Given an image, finds the smallest square shape crop that removes only rgb[0,0,0] pixels.
+///
 /// Due to the image crate, the output is a bounding box where the first two numbers are x and y to start, but the second two are width and height, not x2 and y2.
 pub fn find_bounding_box_for_map(image: &RgbImage) -> BoundingBox {
     let width = image.width();
@@ -82,138 +241,304 @@ pub fn find_bounding_box_for_map(image: &RgbImage) -> BoundingBox {
 pub type TextureListMap = HashMap<String, DynamicImage>;
 
 /// Returns a list of all the filenames in the assets folder hash mapped to the image data respective to that file name.
-pub fn get_texture_list() -> TextureListMap {
-    let dir = fs::read_dir("assets").expect("Unable to read \"assets\" directory.");
+pub fn get_texture_list(assets_dir: &Path) -> Result<TextureListMap> {
+    let dir = fs::read_dir(assets_dir).map_err(|err| McMapperError::io(assets_dir, err))?;
     let list: Vec<String> = dir
         .into_iter()
-        .filter_map(|file_in_dir| match file_in_dir {
-            Ok(f) => Some(f),
-            Err(_) => None,
-        })
+        .filter_map(|file_in_dir| file_in_dir.ok())
+        .filter(|file_entry| file_entry.path().is_file()) // skip the colormap folder and any other folders
         .filter_map(|file_entry| file_entry.file_name().to_str().map(|str| str.to_string()))
+        .filter(|file_name| file_name.ends_with(".png")) // skip the .png.mcmeta files next to animated textures
         .collect();
 
     let mut map: TextureListMap = HashMap::new();
 
     for file_name in list {
-        let path = format!("assets/{}", file_name);
+        let path = assets_dir.join(&file_name).to_string_lossy().to_string();
         let texture_name = file_name.split('.').next().unwrap(); // take the first thing that appears before the file extension
         let minecraft_texture_name = format!("minecraft:{}", texture_name);
-        let image_data = read_texture_from_texture_name(path);
-
-        if image_data.height() > 16 || image_data.width() > 16 {
-            // if the texture loaded is larger than expected, we resize it
-            let resized_image_data = imageops::resize(&image_data, 16, 16, FilterType::Nearest);
-            map.insert(
-                minecraft_texture_name,
-                DynamicImage::from(resized_image_data),
-            );
-        } else {
-            // if its the expected size or smaller, re just load the image into the hash map.
-            map.insert(minecraft_texture_name, image_data);
+        let image_data = read_texture_from_texture_name(path.clone())?;
+        let mcmeta = fs::read(format!("{}.mcmeta", path)).ok();
+        insert_block_texture(&mut map, minecraft_texture_name, image_data, mcmeta.as_deref());
+    }
+
+    insert_error_texture(&mut map);
+
+    // colormaps are optional, if they are missing the biome module falls back to an approximation of the vanilla colormaps.
+    for (key, file_name) in [
+        (GRASS_COLORMAP_KEY, "grass.png"),
+        (FOLIAGE_COLORMAP_KEY, "foliage.png"),
+    ] {
+        if let Ok(colormap) = image::open(assets_dir.join("colormap").join(file_name)) {
+            map.insert(key.to_string(), colormap);
         }
     }
 
-    map.insert(
-        "minecraft:error".to_string(),
-        read_texture_from_texture_name("error.png".to_string()),
-    );
+    Ok(map)
+}
+
+/// Returns a list of the block textures in the given resource packs, e.g. a client jar followed by a server resource pack.
+///
+/// Packs are given lowest priority first, so a texture in a later pack replaces the same texture from the packs before it.
+/// The grass and foliage colormaps are read from the packs as well.
+pub fn get_texture_list_from_packs(packs: &[ResourcePack]) -> Result<TextureListMap> {
+    let mut map: TextureListMap = HashMap::new();
+
+    for pack in packs {
+        let files = pack.read_files(|path| {
+            block_texture_name(path.strip_suffix(".mcmeta").unwrap_or(path)).is_some()
+                || path == "assets/minecraft/textures/colormap/grass.png"
+                || path == "assets/minecraft/textures/colormap/foliage.png"
+        })?;
+        // animation metadata is kept by the path of the texture it belongs to, so the first frame of the texture can be found.
+        let (metas, files): (Vec<_>, Vec<_>) = files.into_iter().partition(|(path, _)| path.ends_with(".mcmeta"));
+        let metas: HashMap<String, Vec<u8>> = metas
+            .into_iter()
+            .map(|(path, bytes)| (path.trim_end_matches(".mcmeta").to_string(), bytes))
+            .collect();
+        for (path, bytes) in files {
+            let image_data = image::load_from_memory(&bytes)
+                .map_err(|err| McMapperError::image(&pack.path.join(&path), err))?;
+            match block_texture_name(&path) {
+                Some(texture_name) => {
+                    insert_block_texture(&mut map, texture_name, image_data, metas.get(&path).map(Vec::as_slice))
+                }
+                None if path.ends_with("grass.png") => {
+                    map.insert(GRASS_COLORMAP_KEY.to_string(), image_data);
+                }
+                None => {
+                    map.insert(FOLIAGE_COLORMAP_KEY.to_string(), image_data);
+                }
+            }
+        }
+    }
 
-    map
+    insert_error_texture(&mut map);
+    Ok(map)
 }
 
-/// Stitches region images together in a not super intelligent way.
-pub fn stitch_region_images(list: &Vec<RegionImage>) -> RgbImage {
-    let region_image_size = 8192; // pixel size of a region
+/// Inserts a block texture into a texture list, replacing any texture of the same name.
+///
+/// Animated textures only keep their first frame. Textures keep their own resolution, they are scaled to the block size when drawn.
+fn insert_block_texture(
+    map: &mut TextureListMap,
+    texture_name: String,
+    image_data: DynamicImage,
+    mcmeta: Option<&[u8]>,
+) {
+    map.insert(texture_name, first_frame(image_data, mcmeta));
+}
 
-    // get us the lowest and highest region coords
-    let min_modifier_x = &list.iter().map(|ri| ri.coordinate.0).min().unwrap(); // left most x coord for a region
-    let min_modifier_y = &list.iter().map(|ri| ri.coordinate.1).min().unwrap();
-    let max_modifier_x = &list.iter().map(|ri| ri.coordinate.0).max().unwrap(); // right most x coord for a region
-    let max_modifier_y = &list.iter().map(|ri| ri.coordinate.1).max().unwrap();
+/// Adds the built in error texture to the texture list, the error texture is drawn for any block without a texture, so rendering cannot go ahead without it.
+fn insert_error_texture(map: &mut TextureListMap) {
+    let texture = image::load_from_memory(ERROR_TEXTURE).expect("the built in error texture is a valid png");
+    map.insert("minecraft:error".to_string(), texture);
+}
 
-    // turn the lowest region coords into offsets so we can center the images
-    let min_coefficient_x = (min_modifier_x * region_image_size).abs(); // modifier will look like a number from -32..=32 or so, so we multiply it by the region size and take abs value
-    let min_coefficient_y = (min_modifier_y * region_image_size).abs();
+/// Adds block color overrides to a texture list, each block is drawn as a solid color instead of its texture, and is not tinted by its biome.
+///
+/// Block names without a namespace are taken to be vanilla blocks, e.g. "water" is the same as "minecraft:water".
+pub fn override_block_colors(map: &mut TextureListMap, colors: &[(String, [u8; 3])]) {
+    for (block, color) in colors {
+        let block = if block.contains(':') {
+            block.clone()
+        } else {
+            format!("minecraft:{}", block)
+        };
+        let pixel = Rgba([color[0], color[1], color[2], 255]);
+        map.insert(
+            format!("{}{}", COLOR_OVERRIDE_PREFIX, block),
+            DynamicImage::from(RgbaImage::from_pixel(1, 1, pixel)),
+        );
+    }
+}
 
-    // final size of the image, it will be larger than needed, but will be cropped later anyway. Making this smaller would be nice though.
-    let width = region_image_size * (min_modifier_x.abs() + 1 + max_modifier_x.abs());
-    let height = region_image_size * (min_modifier_y.abs() + 1 + max_modifier_y.abs());
+/// Turns every texture in a texture list into a single pixel texture of the average color of the texture.
+///
+/// Fully transparent pixels are ignored, so textures with holes like leaves are not darkened, and the share of the texture that is covered is kept as its alpha.
+/// Colormaps are left untouched.
+pub fn average_texture_list(list: &TextureListMap) -> TextureListMap {
+    list.iter()
+        .map(|(name, texture)| {
+            if name.starts_with("colormap:") {
+                return (name.clone(), texture.clone());
+            }
+            let mut sums = [0u64; 3];
+            let mut total_alpha = 0u64;
+            for (_, _, pixel) in texture.pixels() {
+                let alpha = pixel.0[3] as u64;
+                for (sum, channel) in sums.iter_mut().zip(pixel.0.iter()) {
+                    *sum += *channel as u64 * alpha;
+                }
+                total_alpha += alpha;
+            }
+            let color = if total_alpha == 0 {
+                [0, 0, 0]
+            } else {
+                sums.map(|sum| (sum / total_alpha) as u8)
+            };
+            // how much of the texture is covered is kept as the alpha, so a flower is blended over the grass under it instead of hiding it.
+            let coverage = (total_alpha / (texture.width() as u64 * texture.height() as u64).max(1)) as u8;
+            let pixel = Rgba([color[0], color[1], color[2], coverage]);
+            (name.clone(), DynamicImage::from(RgbaImage::from_pixel(1, 1, pixel)))
+        })
+        .collect()
+}
+
+/// Stitches region images together in memory, see the stitch module for stitching worlds too large to fit in memory.
+///
+/// Regions are drawn in order of their origin from top to bottom, and black pixels are left out, so region images that overlap, like isometric ones, are layered correctly.
+/// Returns `McMapperError::NoRegionImages` if there are no region images to stitch.
+pub fn stitch_region_images(list: &[RegionImage]) -> Result<RgbImage> {
+    // get us the area the region images span, in pixels relative to block 0,0
+    let (min_x, min_y, max_x, max_y) = list
+        .iter()
+        .map(|ri| (ri.origin.0, ri.origin.1, ri.origin.0 + ri.image.width() as i64, ri.origin.1 + ri.image.height() as i64))
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+        .ok_or(McMapperError::NoRegionImages)?;
+
+    // final size of the image, only as large as the regions span, it will be cropped later to remove any black borders.
+    let width = max_x - min_x;
+    let height = max_y - min_y;
 
     let mut img: RgbImage = ImageBuffer::new(width as u32, height as u32); // image to return to the function call
-    let mut stitched_images = 0; // count of how many regions have been stitched together, allowing for progress reporting.
 
-    for region in list {
-        let region_x = ((region.coordinate.0 * region_image_size) + min_coefficient_x) as usize;
-        let region_y = ((region.coordinate.1 * region_image_size) + min_coefficient_y) as usize;
+    let mut draw_order: Vec<&RegionImage> = list.iter().collect();
+    draw_order.sort_by_key(|ri| ri.origin.1);
+
+    for region in draw_order {
+        let region_x = (region.origin.0 - min_x) as usize;
+        let region_y = (region.origin.1 - min_y) as usize;
         let pixels = region.image.enumerate_pixels();
 
         for pixel in pixels {
             let color = pixel.2.to_rgb();
+            if color.0 == [0, 0, 0] {
+                continue; // nothing was drawn here, so whatever is behind it stays visible
+            }
             let x = pixel.0 as usize;
             let y = pixel.1 as usize;
             let pixel_x = (region_x + x) as u32;
             let pixel_y = (region_y + y) as u32;
             img.put_pixel(pixel_x, pixel_y, color);
         }
-        stitched_images += 1;
-        println!("Stitch progres: {}/{}", stitched_images, list.len());
     }
-    img
+    Ok(img)
 }
 
-/// Converts a region file into an image and returns it, returns a black image of nothing if the region is not read correctly.
-pub fn region_to_image(region_selected: &RegionFile, texture_list: &TextureListMap) -> RgbImage {
+/// Converts a region file into an image and returns it along with a report of the chunks that could not be read.
+///
+/// Chunks that can not be read are left black, or filled with the error overlay color if the options ask for it.
+/// Blocks are drawn with the top face texture from the block models, if no models were loaded textures are matched by block name.
+/// The edge heights are used to shade the northern border of the region against the region north of it.
+/// If a cached region is given, only its dirty chunks are rendered and the rest are kept from the cached image.
+/// Chunks the region file does not select are skipped without being read.
+pub fn region_to_image(
+    region_selected: &RegionFile,
+    texture_list: &TextureListMap,
+    models: &BlockModels,
+    edge_heights: &RegionEdgeHeights,
+    options: &RenderOptions,
+    cached: Option<CachedRegion>,
+) -> Result<(RgbImage, RegionReport)> {
+    let chunk_pixels = options.block_style.chunk_pixels() as usize;
+    let region_pixels = options.block_style.region_pixels();
+
     let file = &region_selected.file;
     let region_coords = &region_selected.coordinate;
 
-    let mut region = fastanvil::Region::from_stream(file).expect("Unable to stream region file.");
-    let mut images_of_chunks: Vec<(RgbImage, usize, usize)> = vec![]; // image,x,y
+    let mut images_of_chunks: Vec<ChunkImage> = vec![];
+    let mut report = RegionReport::new(region_coords);
+    // a region file that can not be read at all is reported, and the rest of the world is still drawn.
+    let mut region = match fastanvil::Region::from_stream(file) {
+        Ok(region) => Some(region),
+        Err(source) => {
+            report.region_failed(&McMapperError::Region {
+                path: region_selected.path.clone(),
+                source,
+            });
+            None
+        }
+    };
+    let dirty: Vec<bool> = match &cached {
+        None => vec![true; 32 * 32],
+        Some(c) => c.dirty.clone(),
+    };
+    let mut heights: RegionHeights = vec![None; REGION_BLOCK_WIDTH * REGION_BLOCK_WIDTH];
 
     // go through every possible chunk in a region file, which is 0..32 by 0..32
     for chunk_x in 0..32 {
         for chunk_y in 0..32 {
-            let data = match region.read_chunk(chunk_x, chunk_y) {
-                Ok(r) => match r {
-                    None => {
-                        images_of_chunks.push((RgbImage::new(256, 256), chunk_x, chunk_y)); // if the region cant be read for any reason we return a black region image, could be done better i bet?
-                        continue; // early continue on this chunk because it contains no data
+            if !dirty[chunk_y * 32 + chunk_x] {
+                report.cached();
+                // this chunk is kept from the cached image, but if the chunk south of it is rendered again, its heights are needed for shading.
+                if chunk_y < 31 && dirty[(chunk_y + 1) * 32 + chunk_x] {
+                    if let Some(Ok((chunk, surface))) = region.as_mut().map(|region| load_chunk_surface(region, chunk_x, chunk_y)) {
+                        for x in 0..16 {
+                            heights[(chunk_y * 16 + 15) * REGION_BLOCK_WIDTH + chunk_x * 16 + x] =
+                                surface.column_block(&chunk, x, 15, options.surface_mode).map(|(_, y)| y);
+                        }
                     }
-                    Some(vec) => vec,
-                },
-                Err(_) => {
-                    images_of_chunks.push((RgbImage::new(256, 256), chunk_x, chunk_y)); // if the region cant be read for any reason we return a black region image, could be done better i bet?
-                    continue; // early continue on this chunk because it contains no data
+                }
+                continue;
+            }
+            if !region_selected.is_chunk_selected(chunk_x, chunk_y) {
+                // chunks outside of the rendered area are never read, and left black.
+                images_of_chunks.push(ChunkImage::empty(chunk_x, chunk_y, options));
+                continue;
+            }
+            let region = match region.as_mut() {
+                Some(region) => region,
+                None => {
+                    // chunks of a region file that could not be read are drawn like chunks that could not be read.
+                    images_of_chunks.push(ChunkImage::failed(chunk_x, chunk_y, ChunkProblem::IoError, options));
+                    continue;
                 }
             };
 
-
-            match from_bytes(data.as_slice()) {
-                Ok(chunk) => {
-                    // if the chunk is read properly, we render out an image of the chunk.
-                    images_of_chunks.push(chunk_to_image(
-                        chunk,
-                        chunk_x,
-                        chunk_y,
-                        texture_list,
-                        region_coords,
-                    ));
-                }
-                Err(_) => {
-                    // return ImageBuffer::new(8192, 8192);
-                    images_of_chunks.push((RgbImage::new(256, 256), chunk_x, chunk_y)); // if there is an error contained in the chunk data, we push a black image of said chunk instead, and early continue.
+            let (chunk, surface) = match load_chunk_surface(region, chunk_x, chunk_y) {
+                Ok(loaded) => loaded,
+                Err(err) => {
+                    // missing chunks are left black, and so are ones that can not be read unless the error overlay is drawn.
+                    images_of_chunks.push(ChunkImage::failed(chunk_x, chunk_y, err.problem, options));
+                    report.failed(chunk_x, chunk_y, &err);
                     continue;
                 }
-            }
+            };
+
+            // if the chunk is read properly, we render out an image of the chunk.
+            images_of_chunks.push(chunk_to_image(
+                chunk,
+                &surface,
+                chunk_x,
+                chunk_y,
+                texture_list,
+                models,
+                options,
+            )?);
+            report.rendered();
         }
     }
 
-    let mut img: RgbImage = ImageBuffer::new(8192, 8192); // 4096 = 16 chunk images * 16 chunks total
+    let mut img: RgbImage = match cached {
+        None => ImageBuffer::new(region_pixels, region_pixels), // 32 chunk images * 32 chunks total
+        Some(c) => c.image,
+    };
 
     for chunk in images_of_chunks {
-        let block_x = chunk.1 * 256;
-        let block_y = chunk.2 * 256;
-        let pixels = chunk.0.enumerate_pixels();
+        let block_x = chunk.chunk_x * chunk_pixels;
+        let block_y = chunk.chunk_y * chunk_pixels;
+
+        // copy the heights of the chunk into the heights of the whole region, so shading can compare blocks across chunk borders
+        for z in 0..16 {
+            for x in 0..16 {
+                let region_x = chunk.chunk_x * 16 + x;
+                let region_z = chunk.chunk_y * 16 + z;
+                heights[region_z * REGION_BLOCK_WIDTH + region_x] = chunk.heights[z * 16 + x];
+            }
+        }
+
+        let pixels = chunk.image.enumerate_pixels();
 
         for pixel in pixels {
             let color = pixel.2.to_rgb();
@@ -224,103 +549,313 @@ pub fn region_to_image(region_selected: &RegionFile, texture_list: &TextureListM
             img.put_pixel(pixel_x, pixel_y, color);
         }
     }
-    img
+
+    apply_hillshading_to_chunks(
+        &mut img,
+        &heights,
+        edge_heights.get(&region_coords.north()),
+        options.block_style.block_pixels(),
+        &dirty,
+    );
+    Ok((img, report))
 }
 
+/// Surface heights of every column in a chunk, indexed by z * 16 + x.
+pub type ChunkHeights = [Option<isize>; 256];
 
+/// An image of a single chunk, along with the surface heights it was rendered from and where it belongs inside of its region.
+pub struct ChunkImage {
+    /// The rendered image of the chunk.
+    pub image: RgbImage,
+    /// The height of the top block of each column in the chunk, none if the column is empty.
+    pub heights: ChunkHeights,
+    /// The x coordinate of the chunk inside of its region.
+    pub chunk_x: usize,
+    /// The y (z in minecraft) coordinate of the chunk inside of its region.
+    pub chunk_y: usize,
+}
+
+impl ChunkImage {
+    /// A black chunk image with no heights, used for chunks that are missing or could not be read.
+    pub fn empty(chunk_x: usize, chunk_y: usize, options: &RenderOptions) -> Self {
+        let chunk_pixels = options.block_style.chunk_pixels();
+        Self {
+            image: RgbImage::new(chunk_pixels, chunk_pixels),
+            heights: [None; 256],
+            chunk_x,
+            chunk_y,
+        }
+    }
+
+    /// The image of a chunk that could not be read, black unless the options ask for the error overlay and the chunk exists.
+    pub fn failed(
+        chunk_x: usize,
+        chunk_y: usize,
+        problem: ChunkProblem,
+        options: &RenderOptions,
+    ) -> Self {
+        let mut chunk = Self::empty(chunk_x, chunk_y, options);
+        if options.error_overlay && problem != ChunkProblem::NotGenerated {
+            chunk
+                .image
+                .pixels_mut()
+                .for_each(|pixel| *pixel = Rgb(ERROR_OVERLAY_COLOR));
+        }
+        chunk
+    }
+}
 
 /// convert a chunk to an image, the chunk x and chunk y are purely for file naming and image placement in the region file..
+///
+/// The chunk surface must have been read from the same chunk, it is used to find the top block of each column.
 pub fn chunk_to_image(
-    chunk: CurrentJavaChunk,
+    chunk: WorldChunk,
+    surface: &ChunkSurface,
     chunk_x: usize,
     chunk_y: usize,
     texture_list: &TextureListMap,
-    _region_coords: &ChunkCoordinate,
-) -> (RgbImage, usize, usize) {
-    let mut flattened_blocks: HashMap<(usize, usize), Block> = HashMap::new();
+    models: &BlockModels,
+    options: &RenderOptions,
+) -> Result<ChunkImage> {
+    let block_pixels = options.block_style.block_pixels();
+    let chunk_pixels = options.block_style.chunk_pixels();
+    let mut flattened_blocks: HashMap<(usize, usize), FlattenedBlock> = HashMap::new();
+    let mut heights: ChunkHeights = [None; 256];
+    let mut transparent_textures: HashMap<*const DynamicImage, bool> = HashMap::new(); // whether each texture seen so far has see-through pixels
 
     // loop to flatten the chunk and only take blocks that are seeing the sky.
     for x in 0..16 {
         for z in 0..16 {
-            for y in (0..319).rev() {
-                // go from top to bottom, cause top of map is most likely air and we stop when we find something.
-                if let Some(b) = chunk.block(x, y, z) {
-                    if b.name().ne("minecraft:air") && b.name().ne("minecraft:cave_air") {
-                        flattened_blocks.insert((x, z), b.clone());
-                        break;
+            if let Some((b, y)) = surface.column_block(&chunk, x, z, options.surface_mode) {
+                // water and glass are drawn over the first block under them, so the sea floor shows through shallow water.
+                let under = if options.opaque_water || !is_translucent(b) {
+                    None
+                } else {
+                    surface.block_under_translucent(&chunk, x, y, z)
+                };
+                let ((top_block, top_y), cover) = match under {
+                    None => ((b, y), None),
+                    Some(floor) => {
+                        let cover_texture = top_texture(texture_list, models, b)?.0;
+                        let cover_tint = block_tint(b.name(), chunk.biome(x, y, z), texture_list);
+                        (floor, Some((cover_texture, cover_tint, translucent_opacity(y - floor.1))))
                     }
+                };
+
+                // cutout textures like flowers, torches and leaves have see-through pixels, so the blocks under them are drawn too.
+                let mut layers = vec![];
+                let mut layer = Some((top_block, top_y));
+                while let Some((layer_block, layer_y)) = layer {
+                    let (texture, rotation) = top_texture(texture_list, models, layer_block)?;
+                    // grass, leaves and water textures are grayscale, and need to be colored by the biome they are in.
+                    let tint = block_tint(layer_block.name(), chunk.biome(x, layer_y, z), texture_list);
+                    layers.push(DrawLayer { texture, rotation, tint });
+                    let transparent = *transparent_textures
+                        .entry(texture as *const DynamicImage)
+                        .or_insert_with(|| has_transparency(texture));
+                    layer = if layers.len() < MAX_CUTOUT_LAYERS && transparent {
+                        surface.block_at_or_below(&chunk, x, layer_y - 1, z)
+                    } else {
+                        None
+                    };
                 }
+                flattened_blocks.insert((x, z), (layers, cover));
+                heights[z * 16 + x] = Some(y); // the surface of the water is what gets shaded, not the floor under it
             }
         }
     }
 
-    let mut img: RgbImage = ImageBuffer::new(256, 256);
-    for block in flattened_blocks {
-        let block_x = block.0 .0 * 16;
-        let block_y = block.0 .1 * 16;
-        let mc_block = &block.1;
-        let texture = match texture_list.get(mc_block.name()) {
-            None => {
-                // this function slows down the program a good amount in terms of chunk rendering, worth the cost for the easier output though.
-                match search_texture_map(texture_list, mc_block.name()) {
-                    None => {
-                        // #[cfg(debug_assertions)]
-                        // println!("no texture for: {:?}, using error texture: \n", mc_block);
-                        // panic!("unable to find texture, even after searched texture: {:?}", mc_block);
-                        texture_list.get("minecraft:error").expect("Unable to get error texture, make sure error.png is present in the program.")
+    let mut img: RgbImage = ImageBuffer::new(chunk_pixels, chunk_pixels);
+    for ((column_x, column_z), (layers, cover)) in flattened_blocks {
+        // loop to take the respective block textures and place them in the place the blocks occur in.
+        for y in 0..block_pixels {
+            for x in 0..block_pixels {
+                // only the layers down to the first opaque pixel can be seen, which is usually just the top one.
+                let mut colors: Vec<Rgba<u8>> = vec![];
+                for layer in &layers {
+                    let color = layer.color_at(x, y, block_pixels);
+                    colors.push(color);
+                    if color.0[3] == 255 {
+                        break;
                     }
-                    Some(tex) => {
-                        // panic!("unable to find texture, found searched texture: {:?}", mc_block);
-                        tex
+                }
+                let mut color = match colors.as_slice() {
+                    [only] if only.0[3] == 255 => only.to_rgb(),
+                    _ => {
+                        // layers are composited from the lowest one up, whatever is still see-through at the end is drawn over black.
+                        let mut color = [0.0f32; 3];
+                        for over in colors.iter().rev() {
+                            let alpha = over.0[3] as f32 / 255.0;
+                            for (channel, value) in color.iter_mut().enumerate() {
+                                *value = (over.0[channel] as f32).mul_add(alpha, *value * (1.0 - alpha));
+                            }
+                        }
+                        Rgb(color.map(|value| value.round() as u8))
                     }
+                };
+                if let Some((cover_texture, cover_tint, opacity)) = cover {
+                    let cover_layer = DrawLayer {
+                        texture: cover_texture,
+                        rotation: 0,
+                        tint: cover_tint,
+                    };
+                    color = blend(color, cover_layer.color_at(x, y, block_pixels).to_rgb(), opacity);
                 }
+                img.put_pixel(column_x as u32 * block_pixels + x, column_z as u32 * block_pixels + y, color);
             }
-            Some(tex) => tex,
-        };
-        // loop to take the respective block textures and place them in the place the blocks occur in.
-        for pixel in texture.pixels() {
-            let color = pixel.2.to_rgb();
-            let x = pixel.0 as usize;
-            let y = pixel.1 as usize;
-            let pixel_x = (block_x + x) as u32;
-            let pixel_y = (block_y + y) as u32;
+        }
+    }
+    Ok(ChunkImage {
+        image: img,
+        heights,
+        chunk_x,
+        chunk_y,
+    })
+}
+
+/// The most blocks drawn on top of each other in one column when the ones above have see-through pixels, so a tall stack of leaves stops somewhere.
+const MAX_CUTOUT_LAYERS: usize = 4;
 
-            if pixel_x >= 256 || pixel_y >= 256 {
-                panic!("pixel x or y was >= 256: {:?}", mc_block);
+/// Returns true if any pixel of a texture is not fully opaque.
+fn has_transparency(texture: &DynamicImage) -> bool {
+    texture.color().has_alpha() && texture.pixels().any(|pixel| pixel.2 .0[3] < 255)
+}
+
+/// The texture of one block in a column, drawn under the blocks above it where they are see-through.
+struct DrawLayer<'a> {
+    texture: &'a DynamicImage,
+    rotation: u32,
+    tint: Option<Rgb<u8>>,
+}
+
+impl DrawLayer<'_> {
+    /// Returns the tinted color and alpha of the texture at a pixel of a block drawn block_pixels wide, the texture is scaled to fit the block.
+    fn color_at(&self, x: u32, y: u32, block_pixels: u32) -> Rgba<u8> {
+        let (width, height) = self.texture.dimensions();
+        // the texture is turned clockwise when drawn, so turning the pixel the other way finds where it came from
+        let (texture_x, texture_y) = rotate_texture_pixel(
+            x * width / block_pixels,
+            y * height / block_pixels,
+            width,
+            height,
+            (360 - self.rotation % 360) % 360,
+        );
+        let pixel = self.texture.get_pixel(texture_x, texture_y);
+        match self.tint {
+            None => pixel,
+            Some(tint) => {
+                let tinted = multiply_tint(pixel.to_rgb(), tint);
+                Rgba([tinted.0[0], tinted.0[1], tinted.0[2], pixel.0[3]])
             }
+        }
+    }
+}
 
-            img.put_pixel(pixel_x, pixel_y, color);
+/// The blocks drawn for a column from the top one down, and the translucent texture drawn over them with its tint and opacity if there is one.
+type FlattenedBlock<'a> = (Vec<DrawLayer<'a>>, Option<(&'a DynamicImage, Option<Rgb<u8>>, f32)>);
+
+/// Finds the texture on the top face of a block along with how far it is turned clockwise.
+pub(crate) fn top_texture<'a>(
+    texture_list: &'a TextureListMap,
+    models: &BlockModels,
+    mc_block: &Block,
+) -> Result<(&'a DynamicImage, u32)> {
+    if let Some(color) = texture_list.get(&format!("{}{}", COLOR_OVERRIDE_PREFIX, mc_block.name())) {
+        return Ok((color, 0));
+    }
+    // the blockstate and model files say exactly which texture is on top, name matching is only a fallback for blocks without them.
+    let top_face = models.top_face(mc_block).and_then(|face| {
+        texture_list
+            .get(&face.texture)
+            .map(|texture| (texture, face.rotation))
+    });
+    match top_face {
+        Some(found) => Ok(found),
+        None => Ok((texture_by_name(texture_list, models, mc_block)?, 0)),
+    }
+}
+
+/// Finds the texture of a block by matching its name against the texture list, used for blocks whose model has no texture in the list.
+///
+/// Only blocks without a blockstate fall back to textures with a similar name, a block with one is drawn with the error texture instead of a guess.
+fn texture_by_name<'a>(texture_list: &'a TextureListMap, models: &BlockModels, mc_block: &Block) -> Result<&'a DynamicImage> {
+    let texture = match texture_list
+        .get(mc_block.name())
+        .or_else(|| texture_list.get(&format!("{}_top", mc_block.name()))) // blocks like grass_block and logs have their top face in a separate texture
+    {
+        None => {
+            // this function slows down the program a good amount in terms of chunk rendering, worth the cost for the easier output though.
+            let searched = if models.has_block(mc_block.name()) {
+                None
+            } else {
+                search_texture_map(texture_list, mc_block.name())
+            };
+            match searched {
+                None => texture_list.get("minecraft:error").ok_or_else(|| McMapperError::MissingTexture("minecraft:error".to_string()))?,
+                Some(tex) => tex,
+            }
         }
+        Some(tex) => tex,
+    };
+    Ok(texture)
+}
+
+/// Returns where a pixel of a texture ends up once the texture is turned clockwise by the rotation, textures that are not square are never turned.
+pub(crate) const fn rotate_texture_pixel(x: u32, y: u32, width: u32, height: u32, rotation: u32) -> (u32, u32) {
+    if width != height {
+        return (x, y);
+    }
+    match rotation {
+        90 => (width - 1 - y, x),
+        180 => (width - 1 - x, height - 1 - y),
+        270 => (y, height - 1 - x),
+        _ => (x, y),
     }
-    (img, chunk_x, chunk_y)
 }
 
 /// Takes in a list of textures and a search name, and returns either nothing if the texture was not found, or the texture that was found.
 fn search_texture_map<'a>(list: &'a TextureListMap, search_name: &str) -> Option<&'a DynamicImage> {
     for (name, texture) in list {
+        // color overrides only apply to the block they name, not to every block with a similar name.
+        if name.starts_with(COLOR_OVERRIDE_PREFIX) {
+            continue;
+        }
         // if we happen to fine a name of a block that has extra text after, e.g. we are searching for oak_stairs but we find dark_oak_stairs, this should find it and be good enough.
         if name.contains(search_name) || search_name.contains(name) {
             return Some(texture);
         }
-        // if the first search doesnt work, we can shorten the name and remove its modifiers e.g. "dark_oak_stairs" becomes "dark", very approximate but it works for simplicity sake.
-        // TODO: eventually improve this search by instead seeing if it can find a texture that contains the most portions of search name when split by '_', this will require a large search function.
-        //  Unsure if this is worth the runtime costs.
-        match search_name.split('_').next() {
-            None => {}
-            Some(short_name) => {
-                if name.contains(short_name) {
-                    return Some(texture);
-                }
-            }
-        }
     }
     None
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 /// A struct to contain the region coordinate of a region file. e.g. r.-1.2.mca becomes ChunkCoordinate(-1,2)
+///
+/// Despite its name this is the coordinate of a region, see `coordinate::RegionPos` to convert it to blocks, chunks and pixels.
 pub struct ChunkCoordinate(i32, i32);
 
+impl ChunkCoordinate {
+    /// Creates the coordinate of the region with the given region x and z, e.g. -1,2 for r.-1.2.mca.
+    pub const fn new(x: i32, z: i32) -> Self {
+        Self(x, z)
+    }
+
+    /// Returns the region x coordinate.
+    pub const fn x(&self) -> i32 {
+        self.0
+    }
+
+    /// Returns the region z coordinate.
+    pub const fn z(&self) -> i32 {
+        self.1
+    }
+
+    /// Returns the coordinate of the region directly north (negative z) of this one.
+    pub const fn north(&self) -> Self {
+        Self(self.0, self.1 - 1)
+    }
+}
+
 #[derive(Debug)]
 /// A struct to contain a region file header and its respective chunk coordinate.
 pub struct RegionFile {
@@ -328,6 +863,10 @@ pub struct RegionFile {
     pub coordinate: ChunkCoordinate,
     /// A region file read from a minecraft world, an example of a file like this would be in <world name>/<region>/r.0.0.mca
     pub file: File,
+    /// The path the region file was opened from.
+    pub path: PathBuf,
+    /// Which chunks of the region are rendered, indexed by z * 32 + x, every chunk is when this is none, see `RenderArea::select`.
+    pub selected_chunks: Option<Vec<bool>>,
 }
 
 #[derive(Debug)]
@@ -335,10 +874,31 @@ pub struct RegionFile {
 pub struct RegionImage {
     /// The coordinate read from the file, for example r.0.0.mca would have a chunk coordinate of 0,0
     pub coordinate: ChunkCoordinate,
+    /// The position of the top left pixel of the image in the stitched map, see `RenderOptions::region_origin`.
+    pub origin: (i64, i64),
     /// An image of a region, for after the image has been generated from a given region.
     pub image: RgbImage,
 }
 
+impl RegionFile {
+    /// Opens another handle to the same region file, so a region listing can be shared by several renders.
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(Self {
+            coordinate: self.coordinate,
+            file: self.file.try_clone().map_err(|err| McMapperError::io(&self.path, err))?,
+            path: self.path.clone(),
+            selected_chunks: self.selected_chunks.clone(),
+        })
+    }
+
+    /// Returns true if the chunk at this position within the region is rendered.
+    pub fn is_chunk_selected(&self, chunk_x: usize, chunk_z: usize) -> bool {
+        self.selected_chunks
+            .as_ref()
+            .is_none_or(|selected| selected[chunk_z * 32 + chunk_x])
+    }
+}
+
 impl Display for ChunkCoordinate {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{},{}", self.0, self.1)
@@ -352,50 +912,119 @@ impl Display for RegionFile {
 }
 
 /// Get all region files contained within a directory, output a vector full of the file handles and their region coordinates.
+///
 /// e.g. r.0.-1.mca becomes a file header to that file, and a chunk coordinate of 0,-1
-pub fn get_region_files(path: &str) -> Vec<RegionFile> {
-    let dir = fs::read_dir(path)
-        .expect("Unable to read region file directory, check file permissions and that it exists.");
-    let list: Vec<RegionFile> = dir
-        .into_iter()
-        .filter_map(|file_in_dir| match file_in_dir {
-            Ok(f) => Some(f),
-            Err(_) => None,
-        })
-        .filter_map(|file_dir_entry| {
-            let coords: Vec<i32> = file_dir_entry
-                .file_name()
-                .to_str()
-                .unwrap()
-                .split('.')
-                .filter_map(|token| match token.parse() {
-                    Ok(n) => Some(n),
-                    Err(_) => None,
-                })
-                .collect();
-
-            let coord: ChunkCoordinate =
-                ChunkCoordinate(*coords.first()
-                    .expect("Unable to find coordinate of region file, make sure region folder inside world only contains region files. \
-                    Issue could also occur because the program was pointed to a world folder and not the region folder inside the world folder.\
-                    E.g. run program like mcmapper-rs <world name>/region"),
-                                *coords.get(1).unwrap());
-
-            match File::open(file_dir_entry.path()) {
-                Ok(f) => Some(RegionFile {
-                    coordinate: coord,
-                    file: f,
-                }),
-                Err(_) => None,
-            }
-        })
-        .collect();
+pub fn get_region_files(path: &str) -> Result<Vec<RegionFile>> {
+    read_region_files(path, None)
+}
+
+/// Get the region files within a directory that are at least partly inside of an area, limited to the chunks inside of it.
+///
+/// Region files outside of the area are never opened, so a small area of a large world is quick to list.
+pub fn get_region_files_in_area(path: &str, area: &RenderArea) -> Result<Vec<RegionFile>> {
+    read_region_files(path, Some(area))
+}
+
+fn read_region_files(path: &str, area: Option<&RenderArea>) -> Result<Vec<RegionFile>> {
+    let dir = fs::read_dir(path).map_err(|err| McMapperError::io(Path::new(path), err))?;
+    let mut list: Vec<RegionFile> = vec![];
+
+    for file_dir_entry in dir {
+        let file_dir_entry = file_dir_entry.map_err(|err| McMapperError::io(Path::new(path), err))?;
+        let file_name = file_dir_entry.file_name().to_string_lossy().to_string();
+        let coords: Vec<i32> = file_name
+            .split('.')
+            .filter_map(|token| token.parse().ok())
+            .collect();
+
+        let coord = match (coords.first(), coords.get(1)) {
+            (Some(x), Some(z)) => ChunkCoordinate(*x, *z),
+            _ => return Err(McMapperError::BadRegionFileName(file_name)),
+        };
+        if area.is_some_and(|area| !area.overlaps_region(&coord)) {
+            continue;
+        }
 
-    list
+        let region_path = file_dir_entry.path();
+        let file = File::open(&region_path).map_err(|err| McMapperError::io(&region_path, err))?;
+        list.push(RegionFile {
+            coordinate: coord,
+            file,
+            path: region_path,
+            selected_chunks: area.and_then(|area| area.chunk_mask(&coord)),
+        });
+    }
+
+    Ok(list)
 }
 
 /// This function simply opens a given file path and returns the image, more-so used as shorthand than a real function.
-pub fn read_texture_from_texture_name(file: String) -> DynamicImage {
-    image::open(file)
-        .expect("Unable to read file name as an image, check that textures are valid png images.")
+pub fn read_texture_from_texture_name(file: String) -> Result<DynamicImage> {
+    image::open(&file).map_err(|err| McMapperError::image(Path::new(&file), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn block_styles_set_the_size_of_regions() {
+        assert_eq!(BlockStyle::textured().region_pixels(), 8192);
+        assert_eq!(BlockStyle::Textured { size: 32 }.region_pixels(), 16384);
+        assert_eq!(BlockStyle::AverageColor.block_pixels(), 1);
+        assert_eq!(BlockStyle::AverageColor.region_pixels(), 512);
+    }
+
+    #[test]
+    fn average_colors_ignore_transparent_pixels() {
+        let mut leaves = RgbaImage::from_pixel(2, 2, Rgba([0, 0, 0, 0]));
+        leaves.put_pixel(0, 0, Rgba([40, 100, 20, 255]));
+        leaves.put_pixel(1, 1, Rgba([60, 120, 40, 255]));
+        let mut list = TextureListMap::new();
+        list.insert("oak_leaves".to_string(), DynamicImage::from(leaves));
+        list.insert(
+            "glass".to_string(),
+            DynamicImage::from(RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 0]))),
+        );
+
+        let averaged = average_texture_list(&list);
+        let leaves = averaged["oak_leaves"].to_rgb8();
+        assert_eq!(leaves.dimensions(), (1, 1));
+        assert_eq!(leaves.get_pixel(0, 0), &Rgb([50, 110, 30]));
+        assert_eq!(averaged["glass"].to_rgb8().get_pixel(0, 0), &Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn stitched_regions_are_placed_by_their_coordinate() {
+        let region = |x: i32, z: i32, color| RegionImage {
+            coordinate: ChunkCoordinate(x, z),
+            origin: (x as i64 * 2, z as i64 * 2),
+            image: RgbImage::from_pixel(2, 2, Rgb(color)),
+        };
+        let stitched =
+            stitch_region_images(&[region(-1, 0, [200, 0, 0]), region(0, 1, [0, 200, 0])]).unwrap();
+        assert_eq!(stitched.dimensions(), (4, 4));
+        assert_eq!(stitched.get_pixel(1, 1), &Rgb([200, 0, 0]));
+        assert_eq!(stitched.get_pixel(2, 2), &Rgb([0, 200, 0]));
+        assert_eq!(stitched.get_pixel(3, 0), &Rgb([0, 0, 0]));
+    }
+
+    #[test]
+    fn stitching_no_regions_is_an_error() {
+        assert!(matches!(
+            stitch_region_images(&[]),
+            Err(McMapperError::NoRegionImages)
+        ));
+    }
+
+    #[test]
+    fn colormaps_are_not_averaged() {
+        let mut list = TextureListMap::new();
+        list.insert(
+            "colormap:grass".to_string(),
+            DynamicImage::from(RgbaImage::from_pixel(256, 256, Rgba([90, 180, 60, 255]))),
+        );
+        assert_eq!(average_texture_list(&list)["colormap:grass"].width(), 256);
+    }
 }
@@ -3,147 +3,352 @@
 
 //! mcmapper-rs is a program that reads a minecraft world, flattens it, then generates an image from that grouping of blocks and saves it to the system.
 
-extern crate core;
-
-use image::imageops::FilterType;
-use image::{imageops};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::{fs, process};
+use clap::Parser;
+use mcmapper_rs::area::{AreaShape, RenderArea};
+use mcmapper_rs::dimension::Dimension;
+use mcmapper_rs::error::McMapperError;
+use mcmapper_rs::inspect::{inspect_column, region_chunk_stats, ChunkStats};
+use mcmapper_rs::renderer::{MapRenderer, MapSink, RenderProgress, TextureSource, Textures, WorldSource};
+use mcmapper_rs::report::REPORT_FILE_NAME;
+use mcmapper_rs::surface::SurfaceMode;
+use mcmapper_rs::world::{locate_world, read_world_info, WorldInfo};
+use mcmapper_rs::{get_region_files, BlockStyle, Projection, RenderOptions};
 use rayon::prelude::*;
-use std::sync::atomic::{AtomicU32, Ordering};
-use std::sync::Mutex;
-use std::time::SystemTime;
-use std::{env, fs};
-use mcmapper_rs::{find_bounding_box_for_map, get_region_files, get_texture_list, region_file_to_file_name, region_to_image, RegionImage, stitch_region_images};
+use cli::{Cli, Command, InspectCommand, ProfileCommand, RenderArgs, WorldArgs};
+use profile::{read_profiles, RenderProfile};
 
+mod cli;
+mod profile;
 mod timer;
 
 
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    // this might need improvement? Maybe prompt user using stdin?
-    let world_path: String = match args.get(1) {
-        None => {
-            println!("No region folder path given, running with default of \"test/region\" ");
-            if fs::read_dir("test/region").is_ok() {
-                "test/region".to_string()
-            } else {
-                panic!(
-                    "World path not found as command line args and no test world directory exists, please provide the program with a path to a minecraft world."
-                );
-            }
+    // errors are reported without a backtrace, since they are usually caused by a bad path or missing files and not a bug.
+    if let Err(err) = run() {
+        eprintln!("Error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Render(command) => {
+            let output = MapOutput::Stitched {
+                image_name: command.image_name,
+                scales: command.scales,
+            };
+            render_command(&command.render, &output)
         }
-        Some(path) => {
-            if fs::read_dir(path).is_ok() {
-                // path is valid directory
-                path.to_string()
-            } else {
-                panic!("World path found in args, but is not valid");
-            }
+        Command::Tiles(command) => render_command(&command.render, &MapOutput::Tiles),
+        Command::Stats(command) => stats(&command.world),
+        Command::Inspect(command) => inspect(&command),
+        Command::Profile(command) => render_profiles(&command),
+    }
+}
+
+/// Renders the world given on the command line.
+fn render_command(args: &RenderArgs, map_output: &MapOutput) -> Result<(), Box<dyn Error>> {
+    let world = load_world(&args.world)?;
+    let textures = load_textures(&texture_source(args))?;
+    render(args, map_output, &world, &textures)
+}
+
+/// What a render is saved as.
+enum MapOutput {
+    /// A single stitched image, saved once for each of the scales it is shrunk by.
+    Stitched { image_name: String, scales: Vec<u32> },
+    /// A z/x/y tile pyramid along with a viewer for it.
+    Tiles,
+}
+
+/// Reads level.dat and warns if the world is newer than the renderer has been tested with.
+/// It is only used to label the map and mark the spawn, so a world is still read if it is missing or can not be read.
+fn world_info_of(world_dir: &Path) -> Option<WorldInfo> {
+    let world_info = match read_world_info(world_dir) {
+        Ok(world_info) => world_info,
+        Err(err) => {
+            println!("Warning: unable to read level.dat, the map will not be labelled: {}", err);
+            None
         }
     };
+    if let Some(warning) = world_info.as_ref().and_then(WorldInfo::version_warning) {
+        println!("Warning: {}", warning);
+    }
+    world_info
+}
 
-    // checking if output dir exists, if not try to create it, if it cant, then panic the program.
-    match fs::read_dir("output") {
-        Ok(_) => {}
-        Err(err) => match fs::create_dir("output") {
-            Ok(_) => {}
-            Err(err2) => {
-                panic!(
-                    "Error, unable to read or create output directory. \n {} \n {}",
-                    err, err2
-                );
-            }
-        },
+/// Returns the area picked with --region, --bounds and --radius, or none if the whole world should be rendered.
+fn render_area(args: &RenderArgs, world_info: Option<&WorldInfo>) -> Result<Option<RenderArea>, String> {
+    let mut shapes: Vec<AreaShape> = args.regions.iter().map(|(x, z)| AreaShape::Region(*x, *z)).collect();
+    if let Some((corner, opposite)) = args.bounds {
+        shapes.push(AreaShape::rectangle(corner, opposite));
+    }
+    if let Some(radius) = args.radius {
+        // without --center the radius is measured from the world spawn, e.g. to render just the town around it.
+        let center = match (args.center, world_info) {
+            (Some(center), _) => center,
+            (None, Some(world_info)) => (world_info.spawn.0, world_info.spawn.2),
+            (None, None) => return Err("--radius without --center needs the level.dat of the world to find its spawn.".to_string()),
+        };
+        shapes.push(AreaShape::Circle { center, radius });
     }
+    Ok(if shapes.is_empty() { None } else { Some(RenderArea::new(shapes)) })
+}
 
-    // check if assets folder is present, if not try to create it, and if that cant happen panic the program
-    match fs::read_dir("assets") {
-        Ok(_) => {}
-        Err(err) => {
-            match fs::create_dir("assets") {
-                Ok(_) => {}
-                Err(err2) => {
-                    panic!("Unable to create assets folder, missing permissions? Try making assets folder and putting minecraft block textures in it. {}", err2);
-                }
-            }
-            panic!("No assets folder present, please place minecraft assets in assets folder and run program again. {}", err);
+/// Reads a world, renders of part of it only draw the chunks in their area.
+fn load_world(world: &WorldArgs) -> Result<WorldSource, Box<dyn Error>> {
+    let (world_dir, region_path, dimension) = locate_world(&world.world, world.dimension.clone())?;
+    let world_info = world_info_of(&world_dir);
+    let regions = get_region_files(&region_path.to_string_lossy())?;
+    Ok(WorldSource::new(world_dir, dimension, world_info, regions))
+}
+
+/// Where the textures of a render are read from, renders with the same source share their textures.
+fn texture_source(args: &RenderArgs) -> TextureSource {
+    TextureSource {
+        // resource packs to read textures from instead of the assets folder, lowest priority first, e.g. --pack client.jar --pack server_pack.zip
+        assets: args.assets.clone(),
+        packs: args.packs.clone(),
+        // drawing one pixel per block uses the average color of each texture.
+        average: args.pixel,
+        // --color draws a block as a solid color, e.g. to make water stand out on an overview map.
+        colors: args.colors.clone(),
+    }
+}
+
+fn load_textures(source: &TextureSource) -> Result<Textures, Box<dyn Error>> {
+    // check if assets folder is present, if not try to create it, and if that cant happen stop the program
+    if source.packs.is_empty() {
+        if let Err(err) = fs::read_dir(&source.assets) {
+            fs::create_dir_all(&source.assets).map_err(|err| McMapperError::io(&source.assets, err))?;
+            return Err(format!("No assets folder present, please place minecraft assets in {}, or pass a client jar with --pack, and run program again. {}", source.assets.display(), err).into());
         }
     }
 
-    let list = get_region_files(&world_path);
     println!("Discovering texture files");
-    let texture_list = get_texture_list();
-    for region_file in &list {
-        println!("Region file found: {}", region_file);
-    }
-    println!("Length of region file list: {}", list.len());
-
-    let region_images: Mutex<Vec<RegionImage>> = Mutex::new(vec![]); // vector full of all the images that are generated from the region files
-    let threads_finished: AtomicU32 = AtomicU32::new(1); // number of threads that are finished
-    let number_of_regions = list.len() as u32; // number of regions to calculate images for.
-
-    list.into_par_iter()
-        .enumerate()
-        .for_each(|(index, region)| {
-            println!("Thread {} started.\n", index);
-            let region_image = region_to_image(&region, &texture_list); // generate the image of a region
-            let file_name = region_file_to_file_name(&region); // get the file name that the region should have
-
-            region_image
-                .save(format!("./output/{}", file_name))
-                .expect("Unable to save region image to system. Missing permissions?"); // save the region image that was generated
-
-            region_images.lock().unwrap().push(RegionImage {
-                coordinate: region.coordinate,
-                image: region_image,
-            }); // add the image of the region to the region images vector so we can stitch them all together
-            println!("Thread {} ended.\n", index);
-            println!(
-                "Progress: {}/{}.\n",
-                threads_finished.load(Ordering::Relaxed),
-                number_of_regions
-            ); // print out the progress of how many threads are done versus not done.
-
-            threads_finished.fetch_add(1, Ordering::Relaxed); // add to the number of threads that have concluded
-        });
-
-    println!("Stitching regions...");
-    let start_stitch_time = SystemTime::now();
-
-    let full_map_image = stitch_region_images(&region_images.lock().unwrap()); // generate the full map image from all the region images
+    Ok(source.load()?)
+}
 
-    println!(
-        "Stitch time: {:.2} seconds",
-        SystemTime::now()
-            .duration_since(start_stitch_time)
-            .unwrap()
-            .as_secs_f32()
-    );
+/// Renders every profile in a profile file, or the named ones, reading each world and set of textures only once.
+fn render_profiles(command: &ProfileCommand) -> Result<(), Box<dyn Error>> {
+    let profiles = read_profiles(&command.config, &command.profiles)?;
+    let mut worlds: HashMap<(PathBuf, Option<Dimension>), WorldSource> = HashMap::new();
+    let mut textures: HashMap<TextureSource, Textures> = HashMap::new();
+
+    for RenderProfile { name, args, output: map_output } in &profiles {
+        println!("Rendering profile \"{}\"", name);
+        let world = match worlds.entry((args.world.world.clone(), args.world.dimension.clone())) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(load_world(&args.world)?),
+        };
+        let profile_textures = match textures.entry(texture_source(args)) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let loaded = load_textures(entry.key())?;
+                entry.insert(loaded)
+            }
+        };
+        render(args, map_output, world, profile_textures)
+            .map_err(|err| format!("Profile \"{}\": {}", name, err))?;
+    }
+    println!("Rendered {} profiles.", profiles.len());
+    Ok(())
+}
 
-    println!("Cropping and saving full map image...");
+fn render(
+    args: &RenderArgs,
+    map_output: &MapOutput,
+    world: &WorldSource,
+    textures: &Textures,
+) -> Result<(), Box<dyn Error>> {
+    let rendered_dimension = &world.dimension;
+    if let Some(world_info) = &world.world_info {
+        println!("Rendering \"{}\", {}", world_info.name, world_info.description());
+    }
 
-    // crop the image to the bounding box we calculate for the full image
-    let crop = find_bounding_box_for_map(&full_map_image);
-    let cropped_full_map_image =
-        imageops::crop_imm(&full_map_image, crop.0 .0, crop.0 .1, crop.1 .0, crop.1 .1).to_image();
-    cropped_full_map_image
-        .save("./output/cropped_all_regions_massive.png")
-        .expect("Unable to save image to system, missing permissions?");
+    // drawing one pixel per block makes the output 256 times smaller, which is needed for very large worlds.
+    let render_options = RenderOptions {
+        block_style: if args.pixel {
+            BlockStyle::AverageColor
+        } else {
+            // high resolution packs can keep their detail by drawing each block larger, e.g. --texture-size 32 for a 32x pack.
+            BlockStyle::Textured { size: args.texture_size }
+        },
+        // the nether roof would hide everything, so the scan starts under it instead, --skip-ceiling does the same for datapack dimensions.
+        // --max-y draws a slice of the world from that y level down instead, which is used for mapping mines and caves.
+        surface_mode: if let Some(max_y) = args.max_y {
+            SurfaceMode::Slice {
+                max_y,
+                air_above_only: args.air_above_only,
+            }
+        } else if args.skip_ceiling || rendered_dimension.has_ceiling() {
+            SurfaceMode::BelowCeiling
+        } else {
+            SurfaceMode::Sky
+        },
+        // --isometric draws every block as a cube seen from the south east instead of a square seen from above.
+        projection: if args.isometric {
+            Projection::Isometric
+        } else {
+            Projection::TopDown
+        },
+        // water, ice and glass are blended over the blocks under them unless --opaque-water is given.
+        opaque_water: args.opaque_water,
+        // --error-overlay fills chunks that could not be read with a bright color, so corruption stands out on the map.
+        error_overlay: args.error_overlay,
+    };
 
-    println!("Scaling and saving newly cropped image...");
+    // each dimension is saved in its own folder inside of the output folder so their regions and render caches do not mix.
+    let output_dir = match rendered_dimension.output_folder_name() {
+        None => args.output.clone(),
+        Some(name) => args.output.join(name),
+    };
 
-    let scaled_full_map_image = imageops::resize(
-        &cropped_full_map_image,
-        cropped_full_map_image.width() / 8,
-        cropped_full_map_image.height() / 8,
-        FilterType::Nearest,
-    ); // scale the image down a good amount for distribution reasons.
+    let sinks: Vec<MapSink> = match map_output {
+        // a tile pyramid replaces the single huge image, as it can be viewed in a browser no matter how big the world is.
+        MapOutput::Tiles => vec![MapSink::Tiles {
+            folder_name: "tiles".to_string(),
+        }],
+        MapOutput::Stitched { image_name, scales } => {
+            // the one pixel per block image is already small enough to distribute, so there is no need to scale it down by default.
+            let scales = match (scales.is_empty(), render_options.block_style) {
+                (false, _) => scales.clone(),
+                (true, BlockStyle::AverageColor) => vec![1],
+                (true, BlockStyle::Textured { .. }) => vec![1, 8],
+            };
+            scales
+                .into_iter()
+                .map(|scale| MapSink::Image {
+                    file_name: if scale == 1 {
+                        format!("{}.png", image_name)
+                    } else {
+                        format!("{}_downscaled_{}.png", image_name, scale)
+                    },
+                    downscale: scale,
+                })
+                .collect()
+        }
+    };
 
-    scaled_full_map_image
-        .save("./output/cropped_all_regions_tenth.png")
-        .expect("Unable to save image to system, missing permissions?");
+    let mut renderer = MapRenderer::new(world, textures)
+        .options(render_options)
+        .output_dir(&output_dir)
+        .players(args.players)
+        .full_render(args.full_render)
+        .on_progress(print_progress);
+    for sink in sinks {
+        renderer = renderer.sink(sink);
+    }
+    // --region, --bounds and --radius limit the render to part of the world, chunks outside of it are not read.
+    if let Some(area) = render_area(args, world.world_info.as_ref())? {
+        renderer = renderer.area(area);
+    }
+    if let Some(threads) = args.threads {
+        renderer = renderer.threads(threads);
+    }
+    let rendered = renderer.render()?;
 
+    if rendered.report.has_failures() {
+        println!(
+            "Warning: {} chunks and {} region files could not be read, see {} for which ones and why.",
+            rendered.report.chunks.failed(),
+            rendered.report.unreadable_regions,
+            output_dir.join(REPORT_FILE_NAME).display()
+        );
+    }
+    for (tile_dir, pyramid) in &rendered.tiles {
+        println!(
+            "Wrote {} tiles over {} zoom levels, open {} to view them.",
+            pyramid.tile_count,
+            pyramid.max_zoom + 1,
+            tile_dir.join("index.html").display()
+        );
+    }
     println!("Done!");
+    Ok(())
+}
+
+/// Prints what a render is doing as it happens.
+fn print_progress(progress: &RenderProgress) {
+    match progress {
+        RenderProgress::RegionsFound { count } => println!("Rendering {} regions.", count),
+        RenderProgress::RegionCached { coordinate, dirty_chunks } => {
+            println!("Region {} cached, re-rendering {} chunks.", coordinate, dirty_chunks)
+        }
+        RenderProgress::RegionRendered { done, total, .. } => println!("Progress: {}/{}.", done, total),
+        RenderProgress::PlayersFound { count } => println!("Found {} players in the rendered dimension.", count),
+        RenderProgress::Saving { path } => println!("Saving {}...", path.display()),
+        RenderProgress::Stitching { done, total, .. } => println!("Stitch progress: {}/{} regions", done, total),
+        RenderProgress::ZoomLevelWritten { zoom, tiles, .. } => println!("Zoom level {}: {} tiles", zoom, tiles),
+        RenderProgress::Saved { path, elapsed } => {
+            println!("Saved {} in {:.2} seconds.", path.display(), elapsed.as_secs_f32())
+        }
+    }
 }
 
+/// Counts the regions and chunks of a world by the format they are saved in, without rendering it.
+fn stats(world: &WorldArgs) -> Result<(), Box<dyn Error>> {
+    let (world_dir, region_path, dimension) = locate_world(&world.world, world.dimension.clone())?;
+    if let Some(world_info) = world_info_of(&world_dir) {
+        println!("World \"{}\", {}", world_info.name, world_info.description());
+    }
+
+    let list = get_region_files(&region_path.to_string_lossy())?;
+    if list.is_empty() {
+        println!("{} has no regions.", dimension);
+        return Ok(());
+    }
+    let region_stats: Vec<ChunkStats> = list.par_iter().map(region_chunk_stats).collect();
+    let mut chunks = ChunkStats::default();
+    for stats in &region_stats {
+        chunks.add(stats);
+    }
+
+    let min_x = list.iter().map(|region| region.coordinate.x()).min().unwrap_or_default();
+    let max_x = list.iter().map(|region| region.coordinate.x()).max().unwrap_or_default();
+    let min_z = list.iter().map(|region| region.coordinate.z()).min().unwrap_or_default();
+    let max_z = list.iter().map(|region| region.coordinate.z()).max().unwrap_or_default();
+    println!("{}: {} regions, from region {},{} to {},{}", dimension, list.len(), min_x, min_z, max_x, max_z);
+    println!("Blocks from {},{} to {},{}", min_x * 512, min_z * 512, max_x * 512 + 511, max_z * 512 + 511);
+    println!("{} chunks generated", chunks.generated());
+    println!("  {} saved in 1.18 or later", chunks.current);
+    println!("  {} saved in 1.13 to 1.17", chunks.pre18);
+    println!("  {} saved in 1.2 to 1.12", chunks.pre13);
+    println!("  {} could not be read", chunks.unreadable);
+    if chunks.unreadable_regions > 0 {
+        println!("{} region files could not be read at all", chunks.unreadable_regions);
+    }
+    Ok(())
+}
+
+/// Prints the blocks of a column of a world from its highest block down.
+fn inspect(command: &InspectCommand) -> Result<(), Box<dyn Error>> {
+    let (_, region_path, dimension) = locate_world(&command.world.world, command.world.dimension.clone())?;
+    let column = inspect_column(&region_path, command.x, command.z)?;
+    println!(
+        "Column {} {} in {}, chunk saved in {} with status {}",
+        command.x, command.z, dimension, column.saved_in, column.status
+    );
+    match &column.biome {
+        Some(biome) => println!("Biome: {:?}", biome),
+        None => println!("Biome: unknown"),
+    }
+    if column.runs.is_empty() {
+        println!("The column is empty.");
+    }
+    for run in &column.runs {
+        if run.top == run.bottom {
+            println!("  y {}: {}", run.top, run.block);
+        } else {
+            println!("  y {} to {}: {}", run.top, run.bottom, run.block);
+        }
+    }
+    Ok(())
+}
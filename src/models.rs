@@ -0,0 +1,672 @@
+//! Resolving the texture on the top face of a block from the blockstate and model files of resource packs.
+//!
+//! Each block has a blockstate file that picks a model depending on the properties of the block, like the axis of a log.
+//! Models inherit from parent models, and are made of elements with a texture on each face.
+//! The top face of the highest element is what is seen from above, so that is the texture a block is drawn with.
+
+use crate::error::{McMapperError, Result};
+use crate::resources::ResourcePack;
+use fastanvil::Block;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// The texture seen when looking down at a block, and how far it is turned clockwise.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopFace {
+    /// The name of the texture in the texture list, e.g. "minecraft:oak_log_top".
+    pub texture: String,
+    /// How far the texture is turned clockwise, one of 0, 90, 180 or 270.
+    pub rotation: u32,
+}
+
+/// The top faces of every block that has a blockstate file, resolved once when the packs are loaded.
+#[derive(Debug, Default)]
+pub struct BlockModels {
+    blocks: HashMap<String, ResolvedBlock>,
+}
+
+impl BlockModels {
+    /// Reads the blockstates and block models of the given resource packs and resolves the top face of every block state.
+    ///
+    /// Packs are given lowest priority first, the same as when loading textures from packs.
+    pub fn from_packs(packs: &[ResourcePack]) -> Result<Self> {
+        let mut blockstates: HashMap<String, BlockStateFile> = HashMap::new();
+        let mut models: HashMap<String, ModelFile> = HashMap::new();
+
+        for pack in packs {
+            let files = pack
+                .read_files(|path| blockstate_name(path).is_some() || model_name(path).is_some())?;
+            for (path, bytes) in files {
+                // a broken json file in a pack is skipped, the same as minecraft does, and the block falls back to name matching.
+                if let Some(name) = blockstate_name(&path) {
+                    if let Ok(file) = serde_json::from_slice(&bytes) {
+                        blockstates.insert(name, file);
+                    }
+                } else if let Some(name) = model_name(&path) {
+                    if let Ok(file) = serde_json::from_slice(&bytes) {
+                        models.insert(name, file);
+                    }
+                }
+            }
+        }
+
+        Ok(Self::resolve(blockstates, &models))
+    }
+
+    /// Reads the blockstates and block models of an assets folder and resolves the top face of every block state.
+    ///
+    /// The blockstates are read from the blockstates folder inside of it and the models from models/block, the same folders
+    /// as in the client jar, and they belong to minecraft the same as the textures next to them.
+    pub fn from_assets(assets_dir: &Path) -> Result<Self> {
+        let blockstates = read_json_folder(&assets_dir.join("blockstates"), |name| {
+            format!("minecraft:{}", name)
+        })?;
+        let models = read_json_folder(&assets_dir.join("models").join("block"), |name| {
+            format!("minecraft:block/{}", name)
+        })?;
+        Ok(Self::resolve(blockstates, &models))
+    }
+
+    fn resolve(
+        blockstates: HashMap<String, BlockStateFile>,
+        models: &HashMap<String, ModelFile>,
+    ) -> Self {
+        let blocks = blockstates
+            .into_iter()
+            .map(|(name, file)| {
+                let block = resolve_blockstate(file, models);
+                (name, block)
+            })
+            .collect();
+        Self { blocks }
+    }
+
+    /// Returns true if no blockstates were loaded, in which case blocks are matched to textures by name.
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Returns true if the block has a blockstate file.
+    pub fn has_block(&self, name: &str) -> bool {
+        self.blocks.contains_key(name)
+    }
+
+    /// Returns the face seen when looking down at a block, or none if the block has no blockstate or no model with a block texture.
+    pub fn top_face(&self, block: &Block) -> Option<&TopFace> {
+        let resolved = self.blocks.get(block.name())?;
+        let properties = block_properties(block);
+        match resolved {
+            ResolvedBlock::Variants(parts) => parts
+                .iter()
+                .find(|part| part.condition.matches(&properties))
+                .and_then(|part| part.face.as_ref()),
+            ResolvedBlock::Multipart(parts) => parts
+                .iter()
+                .filter(|part| part.face.is_some() && part.condition.matches(&properties))
+                .max_by(|a, b| a.height.total_cmp(&b.height))
+                .and_then(|part| part.face.as_ref()),
+        }
+    }
+
+    /// A stable description of every resolved block, used to tell if the models changed between renders.
+    pub fn fingerprint(&self) -> String {
+        let mut names: Vec<&String> = self.blocks.keys().collect();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| format!("{}{:?}", name, self.blocks[name]))
+            .collect()
+    }
+}
+
+/// Splits the properties out of a block, e.g. "minecraft:oak_log|axis=x" has the properties axis=x.
+fn block_properties(block: &Block) -> Vec<(&str, &str)> {
+    match block.encoded_description().split_once('|') {
+        None => vec![],
+        Some((_, properties)) => properties
+            .split(',')
+            .filter_map(|property| property.split_once('='))
+            .collect(),
+    }
+}
+
+/// Reads every json file in a folder, keyed by the name given to the file name without its extension.
+///
+/// A missing folder has nothing in it, and broken json files are skipped the same as in packs.
+fn read_json_folder<T: for<'de> Deserialize<'de>>(
+    dir: &Path,
+    name: impl Fn(&str) -> String,
+) -> Result<HashMap<String, T>> {
+    let mut files = HashMap::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    for entry in fs::read_dir(dir).map_err(|err| McMapperError::io(dir, err))? {
+        let path = entry.map_err(|err| McMapperError::io(dir, err))?.path();
+        let file_name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(file_name) => file_name,
+            None => continue,
+        };
+        if let Some(stem) = file_name.strip_suffix(".json") {
+            let bytes = fs::read(&path).map_err(|err| McMapperError::io(&path, err))?;
+            if let Ok(file) = serde_json::from_slice(&bytes) {
+                files.insert(name(stem), file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Returns the block name of a blockstate file, e.g. "assets/minecraft/blockstates/stone.json" becomes "minecraft:stone".
+fn blockstate_name(path: &str) -> Option<String> {
+    match path.split('/').collect::<Vec<&str>>().as_slice() {
+        ["assets", namespace, "blockstates", file_name] => file_name
+            .strip_suffix(".json")
+            .map(|name| format!("{}:{}", namespace, name)),
+        _ => None,
+    }
+}
+
+/// Returns the model name of a block model file, e.g. "assets/minecraft/models/block/stone.json" becomes "minecraft:block/stone".
+fn model_name(path: &str) -> Option<String> {
+    match path.split('/').collect::<Vec<&str>>().as_slice() {
+        ["assets", namespace, "models", "block", file_name] => file_name
+            .strip_suffix(".json")
+            .map(|name| format!("{}:block/{}", namespace, name)),
+        _ => None,
+    }
+}
+
+/// Adds the minecraft namespace to a resource location that does not have one, e.g. "block/stone" becomes "minecraft:block/stone".
+fn with_namespace(location: &str) -> String {
+    if location.contains(':') {
+        location.to_string()
+    } else {
+        format!("minecraft:{}", location)
+    }
+}
+
+#[derive(Deserialize)]
+struct BlockStateFile {
+    #[serde(default)]
+    variants: HashMap<String, ModelChoice>,
+    #[serde(default)]
+    multipart: Vec<MultipartCase>,
+}
+
+/// A blockstate can list several models to pick from at random, the first one is always used here.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ModelChoice {
+    One(VariantModel),
+    Many(Vec<VariantModel>),
+}
+
+impl ModelChoice {
+    fn first(&self) -> Option<&VariantModel> {
+        match self {
+            Self::One(model) => Some(model),
+            Self::Many(models) => models.first(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct VariantModel {
+    model: String,
+    #[serde(default)]
+    x: i32,
+    #[serde(default)]
+    y: i32,
+}
+
+#[derive(Deserialize)]
+struct MultipartCase {
+    when: Option<Value>,
+    apply: ModelChoice,
+}
+
+#[derive(Deserialize)]
+struct ModelFile {
+    parent: Option<String>,
+    #[serde(default)]
+    textures: HashMap<String, Value>,
+    elements: Option<Vec<Element>>,
+}
+
+#[derive(Deserialize)]
+struct Element {
+    from: [f32; 3],
+    to: [f32; 3],
+    #[serde(default)]
+    faces: HashMap<String, Face>,
+}
+
+#[derive(Deserialize)]
+struct Face {
+    texture: String,
+    #[serde(default)]
+    rotation: i32,
+}
+
+/// The properties a block needs for a variant or multipart case to apply.
+#[derive(Debug)]
+enum Condition {
+    /// Applies to every block, e.g. the "" variant.
+    Always,
+    /// Each property must have one of the listed values.
+    Properties(Vec<(String, Vec<String>)>),
+    /// Any of the conditions must apply.
+    Or(Vec<Self>),
+    /// All of the conditions must apply.
+    And(Vec<Self>),
+}
+
+impl Condition {
+    /// Parses a variant key, e.g. "axis=x" or "facing=east,half=top".
+    fn from_variant_key(key: &str) -> Self {
+        if key.is_empty() {
+            return Self::Always;
+        }
+        Self::Properties(
+            key.split(',')
+                .filter_map(|property| property.split_once('='))
+                .map(|(name, value)| (name.to_string(), vec![value.to_string()]))
+                .collect(),
+        )
+    }
+
+    /// Parses the "when" of a multipart case, e.g. {"north": "true|tall"} or {"OR": [...]}.
+    fn from_when(when: Option<&Value>) -> Self {
+        let object = match when.and_then(Value::as_object) {
+            None => return Self::Always,
+            Some(object) => object,
+        };
+        let list = |key: &str| -> Option<Vec<Self>> {
+            object.get(key).and_then(Value::as_array).map(|cases| {
+                cases
+                    .iter()
+                    .map(|case| Self::from_when(Some(case)))
+                    .collect()
+            })
+        };
+        if let Some(cases) = list("OR") {
+            return Self::Or(cases);
+        }
+        if let Some(cases) = list("AND") {
+            return Self::And(cases);
+        }
+        Self::Properties(
+            object
+                .iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        Value::String(text) => text.clone(),
+                        other => other.to_string(), // booleans and numbers are allowed unquoted
+                    };
+                    (name.clone(), value.split('|').map(str::to_string).collect())
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns true if a block with the given properties meets the condition.
+    ///
+    /// A property the block does not have only matches its default value, fastanvil strips some properties like waterlogged and powered.
+    fn matches(&self, properties: &[(&str, &str)]) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Properties(required) => required.iter().all(|(name, values)| {
+                let value = match properties.iter().find(|(key, _)| key == name) {
+                    Some((_, value)) => *value,
+                    None => match default_property_value(name) {
+                        Some(value) => value,
+                        None => return false,
+                    },
+                };
+                values.iter().any(|v| v == value)
+            }),
+            Self::Or(cases) => cases.iter().any(|case| case.matches(properties)),
+            Self::And(cases) => cases.iter().all(|case| case.matches(properties)),
+        }
+    }
+}
+
+/// Returns the value of a property that fastanvil strips from blocks, which is the value it has in most of them.
+fn default_property_value(name: &str) -> Option<&'static str> {
+    match name {
+        "waterlogged" | "powered" => Some("false"),
+        _ => None,
+    }
+}
+
+/// A variant or multipart case along with the top face of its model.
+#[derive(Debug)]
+struct ResolvedPart {
+    condition: Condition,
+    face: Option<TopFace>,
+    /// How high up the block the top face is, out of 16, used to pick the part seen from above in multipart blocks.
+    height: f32,
+}
+
+#[derive(Debug)]
+enum ResolvedBlock {
+    /// Only the first variant that matches is used.
+    Variants(Vec<ResolvedPart>),
+    /// Every case that matches is drawn, the one with the highest top face is used.
+    Multipart(Vec<ResolvedPart>),
+}
+
+fn resolve_blockstate(file: BlockStateFile, models: &HashMap<String, ModelFile>) -> ResolvedBlock {
+    if !file.multipart.is_empty() {
+        return ResolvedBlock::Multipart(
+            file.multipart
+                .iter()
+                .map(|case| {
+                    resolve_part(
+                        Condition::from_when(case.when.as_ref()),
+                        &case.apply,
+                        models,
+                    )
+                })
+                .collect(),
+        );
+    }
+
+    // variants are sorted so that the variant picked when several of them match is always the same one.
+    let mut variants: Vec<(&String, &ModelChoice)> = file.variants.iter().collect();
+    variants.sort_by(|a, b| a.0.cmp(b.0));
+    ResolvedBlock::Variants(
+        variants
+            .into_iter()
+            .map(|(key, choice)| resolve_part(Condition::from_variant_key(key), choice, models))
+            .collect(),
+    )
+}
+
+fn resolve_part(
+    condition: Condition,
+    choice: &ModelChoice,
+    models: &HashMap<String, ModelFile>,
+) -> ResolvedPart {
+    let (face, height) = match choice
+        .first()
+        .and_then(|model| resolve_top_face(model, models))
+    {
+        Some((face, height)) => (Some(face), height),
+        None => (None, 0.0),
+    };
+    ResolvedPart {
+        condition,
+        face,
+        height,
+    }
+}
+
+/// Works out the top face of a model, following its parents for textures and elements.
+fn resolve_top_face(
+    variant: &VariantModel,
+    models: &HashMap<String, ModelFile>,
+) -> Option<(TopFace, f32)> {
+    let mut textures: HashMap<&str, &Value> = HashMap::new();
+    let mut elements: Option<&Vec<Element>> = None;
+    let mut next = Some(with_namespace(&variant.model));
+    let mut depth = 0;
+    while let Some(name) = next {
+        depth += 1;
+        let model = match models.get(&name) {
+            Some(model) if depth < 32 => model, // a parent loop in a broken pack should not hang the program
+            _ => break,
+        };
+        for (key, value) in &model.textures {
+            textures.entry(key).or_insert(value); // textures in children replace the ones from their parents
+        }
+        if elements.is_none() {
+            elements = model.elements.as_ref();
+        }
+        next = model.parent.as_deref().map(with_namespace);
+    }
+
+    // rotating a model around the x axis turns a different face to the top, the face that ends up on top and how high it sits are picked here.
+    let x = variant.x.rem_euclid(360);
+    let (face_name, face_height): (&str, fn(&Element) -> f32) = match x {
+        90 => ("south", |e| e.to[2]),
+        180 => ("down", |e| 16.0 - e.from[1]),
+        270 => ("north", |e| 16.0 - e.from[2]),
+        _ => ("up", |e| e.to[1]),
+    };
+
+    let top = elements.and_then(|elements| {
+        elements
+            .iter()
+            .filter_map(|element| {
+                element
+                    .faces
+                    .get(face_name)
+                    .map(|face| (face, face_height(element)))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    });
+
+    match top {
+        Some((face, height)) => {
+            let texture = resolve_texture(&face.texture, &textures)?;
+            let rotation = (variant.y + face.rotation).rem_euclid(360) as u32;
+            Some((TopFace { texture, rotation }, height))
+        }
+        // models without elements, like water or chests, are drawn by the game in code, their particle texture is the closest match.
+        None => resolve_texture("#particle", &textures).map(|texture| {
+            (
+                TopFace {
+                    texture,
+                    rotation: 0,
+                },
+                16.0,
+            )
+        }),
+    }
+}
+
+/// Follows texture variables like "#top" until a texture is found, and turns it into the name used in the texture list.
+/// Returns none if the texture is not a block texture.
+fn resolve_texture(reference: &str, textures: &HashMap<&str, &Value>) -> Option<String> {
+    let mut reference = reference.to_string();
+    for _ in 0..16 {
+        let variable = match reference.strip_prefix('#') {
+            None => break,
+            Some(variable) => variable,
+        };
+        reference = match textures.get(variable)? {
+            Value::String(text) => text.clone(),
+            // newer versions allow a texture to be an object with the texture under "sprite"
+            Value::Object(object) => object.get("sprite")?.as_str()?.to_string(),
+            _ => return None,
+        };
+    }
+    let location = with_namespace(&reference);
+    let (namespace, path) = location.split_once(':')?;
+    let name = path.strip_prefix("block/")?;
+    Some(format!("{}:{}", namespace, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn models() -> HashMap<String, ModelFile> {
+        let files = [
+            (
+                "minecraft:block/cube",
+                r##"{"elements": [{"from": [0, 0, 0], "to": [16, 16, 16], "faces": {
+                    "up": {"texture": "#up"}, "south": {"texture": "#south"}}}]}"##,
+            ),
+            (
+                "minecraft:block/cube_column",
+                r##"{"parent": "block/cube", "textures": {"up": "#end", "south": "#side"}}"##,
+            ),
+            (
+                "minecraft:block/oak_log",
+                r#"{"parent": "minecraft:block/cube_column", "textures": {
+                    "end": "minecraft:block/oak_log_top", "side": "block/oak_log"}}"#,
+            ),
+            (
+                "minecraft:block/slab",
+                r##"{"textures": {"up": "block/stone"}, "elements": [{"from": [0, 0, 0], "to": [16, 8, 16],
+                    "faces": {"up": {"texture": "#up", "rotation": 180}}}]}"##,
+            ),
+            (
+                "minecraft:block/water",
+                r#"{"textures": {"particle": {"sprite": "block/water_still"}}}"#,
+            ),
+            (
+                "minecraft:block/item_textured",
+                r#"{"textures": {"particle": "item/stick"}}"#,
+            ),
+        ];
+        files
+            .into_iter()
+            .map(|(name, json)| (name.to_string(), serde_json::from_str(json).unwrap()))
+            .collect()
+    }
+
+    fn block_models(blockstates: &[(&str, &str)]) -> BlockModels {
+        let models = models();
+        BlockModels {
+            blocks: blockstates
+                .iter()
+                .map(|(name, json)| {
+                    let file = serde_json::from_str(json).unwrap();
+                    (name.to_string(), resolve_blockstate(file, &models))
+                })
+                .collect(),
+        }
+    }
+
+    fn block(json: &str) -> Block {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn top_face(texture: &str, rotation: u32) -> Option<TopFace> {
+        Some(TopFace {
+            texture: texture.to_string(),
+            rotation,
+        })
+    }
+
+    #[test]
+    fn textures_are_resolved_through_parents() {
+        let models = block_models(&[(
+            "minecraft:oak_log",
+            r#"{"variants": {
+                "axis=y": {"model": "minecraft:block/oak_log"},
+                "axis=x": {"model": "minecraft:block/oak_log", "x": 90, "y": 90}}}"#,
+        )]);
+        let upright = block(r#"{"Name": "minecraft:oak_log", "Properties": {"axis": "y"}}"#);
+        assert_eq!(
+            models.top_face(&upright).cloned(),
+            top_face("minecraft:oak_log_top", 0)
+        );
+        // turned on its side the south face, with the bark texture, ends up on top.
+        let sideways = block(r#"{"Name": "minecraft:oak_log", "Properties": {"axis": "x"}}"#);
+        assert_eq!(
+            models.top_face(&sideways).cloned(),
+            top_face("minecraft:oak_log", 90)
+        );
+    }
+
+    #[test]
+    fn models_without_elements_use_their_particle_texture() {
+        let models = block_models(&[
+            (
+                "minecraft:water",
+                r#"{"variants": {"": {"model": "block/water"}}}"#,
+            ),
+            (
+                "minecraft:stick_block",
+                r#"{"variants": {"": {"model": "block/item_textured"}}}"#,
+            ),
+        ]);
+        assert_eq!(
+            models
+                .top_face(&block(r#"{"Name": "minecraft:water"}"#))
+                .cloned(),
+            top_face("minecraft:water_still", 0)
+        );
+        // textures that are not block textures can not be drawn.
+        assert_eq!(
+            models.top_face(&block(r#"{"Name": "minecraft:stick_block"}"#)),
+            None
+        );
+        assert_eq!(
+            models.top_face(&block(r#"{"Name": "minecraft:stone"}"#)),
+            None
+        );
+    }
+
+    #[test]
+    fn multipart_uses_the_highest_matching_part() {
+        let models = block_models(&[(
+            "minecraft:test_block",
+            r#"{"multipart": [
+                {"apply": {"model": "block/slab"}},
+                {"when": {"OR": [{"tall": "true"}, {"up": "true|side"}]}, "apply": {"model": "block/oak_log"}}]}"#,
+        )]);
+        let low = block(
+            r#"{"Name": "minecraft:test_block", "Properties": {"tall": "false", "up": "none"}}"#,
+        );
+        assert_eq!(
+            models.top_face(&low).cloned(),
+            top_face("minecraft:stone", 180)
+        );
+        let high = block(
+            r#"{"Name": "minecraft:test_block", "Properties": {"tall": "false", "up": "side"}}"#,
+        );
+        assert_eq!(
+            models.top_face(&high).cloned(),
+            top_face("minecraft:oak_log_top", 0)
+        );
+    }
+
+    #[test]
+    fn missing_properties_only_match_their_default() {
+        let models = block_models(&[
+            (
+                "minecraft:test_block",
+                r#"{"variants": {"axis=x": {"model": "block/slab"}, "axis=y": {"model": "block/oak_log"}}}"#,
+            ),
+            (
+                "minecraft:test_lamp",
+                r#"{"multipart": [
+                    {"apply": {"model": "block/slab"}},
+                    {"when": {"powered": "true"}, "apply": {"model": "block/oak_log"}}]}"#,
+            ),
+        ]);
+        // a block without an axis matches none of the variants, rather than the first one.
+        assert_eq!(
+            models.top_face(&block(r#"{"Name": "minecraft:test_block"}"#)),
+            None
+        );
+        // fastanvil strips powered, so the block is drawn as unpowered.
+        let lamp = block(r#"{"Name": "minecraft:test_lamp", "Properties": {"powered": "true"}}"#);
+        assert_eq!(
+            models.top_face(&lamp).cloned(),
+            top_face("minecraft:stone", 180)
+        );
+    }
+
+    #[test]
+    fn pack_paths_are_named() {
+        assert_eq!(
+            blockstate_name("assets/minecraft/blockstates/stone.json"),
+            Some("minecraft:stone".to_string())
+        );
+        assert_eq!(
+            model_name("assets/mypack/models/block/ore.json"),
+            Some("mypack:block/ore".to_string())
+        );
+        assert_eq!(model_name("assets/minecraft/models/item/stick.json"), None);
+    }
+}
@@ -0,0 +1,150 @@
+//! Players saved in a world, read from the playerdata folder so their last known positions can be marked on the map.
+//!
+//! Each player is stored in <world>/playerdata/<uuid>.dat as gzipped nbt, which does not hold their name.
+//! Names are looked up in the usercache.json the server keeps next to the world folder, if there is one.
+
+use crate::dimension::Dimension;
+use crate::error::{McMapperError, Result};
+use crate::world::read_gzipped_nbt;
+use crate::RenderOptions;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// The color players are marked with on stitched images.
+pub const PLAYER_MARKER_COLOR: [u8; 3] = [230, 40, 40];
+
+/// A player and where they were when they last logged out, or when the world was last saved.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Player {
+    /// The uuid of the player, taken from the name of their playerdata file.
+    pub uuid: String,
+    /// The name of the player, none if there is no usercache.json entry for them.
+    pub name: Option<String>,
+    /// The dimension the player is in.
+    pub dimension: Dimension,
+    /// The x, y and z block position of the feet of the player.
+    pub position: (f64, f64, f64),
+}
+
+/// The parts of a playerdata file needed to place a player on the map.
+#[derive(Deserialize)]
+struct StoredPlayer {
+    #[serde(rename = "Pos")]
+    pos: Vec<f64>,
+    #[serde(rename = "Dimension")]
+    dimension: Option<StoredDimension>,
+}
+
+/// The dimension a player is in, stored as an id since 1.16 and as a number before it.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredDimension {
+    Id(String),
+    Number(i32),
+}
+
+/// An entry of the usercache.json file a server keeps of the players that have joined it.
+#[derive(Deserialize)]
+struct UserCacheEntry {
+    name: String,
+    uuid: String,
+}
+
+/// A player as written to players.json, along with the pixel of the map they are drawn at.
+#[derive(Serialize)]
+struct PlayerMarker<'a> {
+    #[serde(flatten)]
+    player: &'a Player,
+    /// The full resolution pixel the player is at, relative to the pixel block 0,0 of the world is drawn at, the same pixels the tile viewer uses.
+    map_pixel: (i64, i64),
+}
+
+/// Reads every player in the playerdata folder of a world, a world without one has no players.
+///
+/// Playerdata files that can not be read, like ones that were being written to when the world was copied, are skipped.
+pub fn read_players(world: &Path) -> Result<Vec<Player>> {
+    let playerdata = world.join("playerdata");
+    if !playerdata.is_dir() {
+        return Ok(vec![]);
+    }
+    let names = read_user_cache(world);
+
+    let mut players = vec![];
+    for entry in fs::read_dir(&playerdata).map_err(|err| McMapperError::io(&playerdata, err))? {
+        let path = entry
+            .map_err(|err| McMapperError::io(&playerdata, err))?
+            .path();
+        let uuid = match path.file_name().and_then(|name| name.to_str()) {
+            Some(file_name) => match file_name.strip_suffix(".dat") {
+                Some(uuid) => uuid.to_string(),
+                None => continue, // backups like <uuid>.dat_old
+            },
+            None => continue,
+        };
+        let stored: StoredPlayer = match read_gzipped_nbt(&path) {
+            Ok(stored) => stored,
+            Err(_) => continue,
+        };
+        let (x, y, z) = match stored.pos.as_slice() {
+            [x, y, z] => (*x, *y, *z),
+            _ => continue,
+        };
+        let dimension = match stored.dimension {
+            None => Dimension::Overworld,
+            Some(StoredDimension::Id(id)) => {
+                Dimension::from_id(&id).unwrap_or(Dimension::Overworld)
+            }
+            Some(StoredDimension::Number(-1)) => Dimension::Nether,
+            Some(StoredDimension::Number(1)) => Dimension::End,
+            Some(StoredDimension::Number(_)) => Dimension::Overworld,
+        };
+        players.push(Player {
+            name: names.get(&uuid).cloned(),
+            uuid,
+            dimension,
+            position: (x, y, z),
+        });
+    }
+    players.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.uuid.cmp(&b.uuid)));
+    Ok(players)
+}
+
+/// Reads the player names from usercache.json, which a server keeps in its own folder, next to the world folder.
+/// Returns the names keyed by uuid, or nothing if there is no usercache.json.
+fn read_user_cache(world: &Path) -> HashMap<String, String> {
+    let candidates = [
+        Some(world.join("usercache.json")),
+        world.parent().map(|server| server.join("usercache.json")),
+    ];
+    candidates
+        .into_iter()
+        .flatten()
+        .find_map(|path| fs::read(path).ok())
+        .and_then(|bytes| serde_json::from_slice::<Vec<UserCacheEntry>>(&bytes).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|entry| (entry.uuid, entry.name))
+        .collect()
+}
+
+/// Returns the full resolution pixel a player is drawn at, see `RenderOptions::map_pixel`.
+pub fn player_map_pixel(player: &Player, options: &RenderOptions) -> (i64, i64) {
+    let (x, y, z) = player.position;
+    options.map_pixel(x, y, z)
+}
+
+/// Writes the players to a json file for the tile viewer, along with the pixel of the map each one is at.
+pub fn write_players_json(path: &Path, players: &[Player], options: &RenderOptions) -> Result<()> {
+    let markers: Vec<PlayerMarker> = players
+        .iter()
+        .map(|player| PlayerMarker {
+            player,
+            map_pixel: player_map_pixel(player, options),
+        })
+        .collect();
+    let json =
+        serde_json::to_string_pretty(&markers).map_err(|err| McMapperError::json(path, err))?;
+    fs::write(path, json).map_err(|err| McMapperError::io(path, err))
+}
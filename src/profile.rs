@@ -0,0 +1,251 @@
+//! Render profiles read from a toml file, usually mcmapper.toml, so several maps can be rendered in a single run.
+//!
+//! Each profile is a table under `profiles` named by its key, and takes the same settings as the flags of the render command,
+//! with underscores instead of dashes, e.g.
+//!
+//! ```toml
+//! [profiles.overworld-day]
+//! world = "world"
+//! packs = ["1.19.jar"]
+//! players = true
+//!
+//! [profiles.nether-caves]
+//! world = "world"
+//! dimension = "nether"
+//! max_y = 40
+//! air_above_only = true
+//! format = "tiles"
+//!
+//! [profiles.overview-1px]
+//! world = "world"
+//! pixel = true
+//! colors = { water = "#3f76e4" }
+//!
+//! [profiles.spawn-town]
+//! world = "world"
+//! radius = 300
+//! ```
+
+use crate::cli::{
+    parse_color, parse_dimension, validate_texture_size, BlockBounds, RenderArgs, WorldArgs,
+};
+use crate::MapOutput;
+use mcmapper_rs::DEFAULT_TEXTURE_SIZE;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A profile read from a profile file, ready to be rendered.
+pub struct RenderProfile {
+    /// The name of the profile, its key in the profile file.
+    pub name: String,
+    /// The settings of the profile, the same as if they were given to the render command.
+    pub args: RenderArgs,
+    /// What the profile is saved as.
+    pub output: MapOutput,
+}
+
+/// A profile file, holding profiles keyed by their name.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ProfileFile {
+    #[serde(default)]
+    profiles: BTreeMap<String, Profile>,
+}
+
+/// What a profile is saved as.
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum ProfileFormat {
+    /// A stitched image, like the render command.
+    #[default]
+    Image,
+    /// A tile pyramid, like the tiles command.
+    Tiles,
+}
+
+/// A named set of render settings, see the flags of the render command for what each one does.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct Profile {
+    world: PathBuf,
+    dimension: Option<String>,
+    /// Defaults to a folder named after the profile inside of the output folder, so profiles do not overwrite each other.
+    output: Option<PathBuf>,
+    #[serde(default = "default_assets")]
+    assets: PathBuf,
+    #[serde(default)]
+    packs: Vec<PathBuf>,
+    /// Block names mapped to the hex color they are drawn with.
+    #[serde(default)]
+    colors: BTreeMap<String, String>,
+    #[serde(default)]
+    pixel: bool,
+    #[serde(default = "default_texture_size")]
+    texture_size: u32,
+    #[serde(default)]
+    isometric: bool,
+    max_y: Option<isize>,
+    #[serde(default)]
+    air_above_only: bool,
+    #[serde(default)]
+    skip_ceiling: bool,
+    #[serde(default)]
+    opaque_water: bool,
+    #[serde(default)]
+    error_overlay: bool,
+    #[serde(default)]
+    players: bool,
+    #[serde(default)]
+    full_render: bool,
+    threads: Option<usize>,
+    #[serde(default)]
+    regions: Vec<(i32, i32)>,
+    bounds: Option<BlockBounds>,
+    radius: Option<u32>,
+    center: Option<(i32, i32)>,
+    #[serde(default)]
+    format: ProfileFormat,
+    #[serde(default = "default_image_name")]
+    image_name: String,
+    #[serde(default)]
+    scales: Vec<u32>,
+}
+
+fn default_assets() -> PathBuf {
+    PathBuf::from("assets")
+}
+
+const fn default_texture_size() -> u32 {
+    DEFAULT_TEXTURE_SIZE
+}
+
+fn default_image_name() -> String {
+    "map".to_string()
+}
+
+impl Profile {
+    /// Checks the settings of the profile and turns them into the arguments of the render command.
+    fn into_render_args(self, name: &str) -> Result<(RenderArgs, MapOutput), String> {
+        let dimension = match &self.dimension {
+            None => None,
+            Some(id) => Some(parse_dimension(id)?),
+        };
+        let texture_size = validate_texture_size(self.texture_size)
+            .map_err(|err| format!("texture_size {}", err))?;
+        if self.air_above_only && self.max_y.is_none() {
+            return Err("air_above_only needs max_y to be set".to_string());
+        }
+        if self.center.is_some() && self.radius.is_none() {
+            return Err("center needs radius to be set".to_string());
+        }
+        if self.scales.contains(&0) {
+            return Err("scales must be 1 or more".to_string());
+        }
+        let colors = self
+            .colors
+            .iter()
+            .map(|(block, color)| Ok((block.clone(), parse_color(color)?)))
+            .collect::<Result<Vec<(String, [u8; 3])>, String>>()?;
+
+        let args = RenderArgs {
+            world: WorldArgs {
+                world: self.world,
+                dimension,
+            },
+            output: self
+                .output
+                .unwrap_or_else(|| Path::new("output").join(name)),
+            assets: self.assets,
+            packs: self.packs,
+            colors,
+            pixel: self.pixel,
+            texture_size,
+            isometric: self.isometric,
+            max_y: self.max_y,
+            air_above_only: self.air_above_only,
+            skip_ceiling: self.skip_ceiling,
+            opaque_water: self.opaque_water,
+            error_overlay: self.error_overlay,
+            players: self.players,
+            full_render: self.full_render,
+            threads: self.threads,
+            regions: self.regions,
+            bounds: self.bounds,
+            radius: self.radius,
+            center: self.center,
+        };
+        let output = match self.format {
+            ProfileFormat::Image => MapOutput::Stitched {
+                image_name: self.image_name,
+                scales: self.scales,
+            },
+            ProfileFormat::Tiles => MapOutput::Tiles,
+        };
+        Ok((args, output))
+    }
+}
+
+/// Reads the profiles of a profile file, only keeping the named ones if any names are given.
+///
+/// Profiles are returned in order of their name.
+pub fn read_profiles(path: &Path, names: &[String]) -> Result<Vec<RenderProfile>, Box<dyn Error>> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| format!("Unable to read profile file {}: {}", path.display(), err))?;
+    let file: ProfileFile = toml::from_str(&text)
+        .map_err(|err| format!("Unable to read profile file {}: {}", path.display(), err))?;
+
+    if let Some(missing) = names.iter().find(|name| !file.profiles.contains_key(*name)) {
+        let known: Vec<&str> = file.profiles.keys().map(String::as_str).collect();
+        return Err(format!(
+            "No profile named \"{}\" in {}, the profiles are: {}",
+            missing,
+            path.display(),
+            known.join(", ")
+        )
+        .into());
+    }
+
+    let mut profiles = vec![];
+    for (name, profile) in file.profiles {
+        if !names.is_empty() && !names.contains(&name) {
+            continue;
+        }
+        let (args, output) = profile
+            .into_render_args(&name)
+            .map_err(|err| format!("Profile \"{}\" in {}: {}", name, path.display(), err))?;
+        profiles.push(RenderProfile { name, args, output });
+    }
+    if profiles.is_empty() {
+        return Err(format!("{} has no profiles in it.", path.display()).into());
+    }
+    Ok(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(toml: &str) -> Result<(RenderArgs, MapOutput), String> {
+        let file: ProfileFile = toml::from_str(toml).unwrap();
+        let (name, profile) = file.profiles.into_iter().next().unwrap();
+        profile.into_render_args(&name)
+    }
+
+    #[test]
+    fn texture_sizes_must_be_a_power_of_two() {
+        let args = match profile("[profiles.big]\nworld = \"world\"\ntexture_size = 32\n") {
+            Ok((args, _)) => args,
+            Err(err) => panic!("{}", err),
+        };
+        assert_eq!(args.texture_size, 32);
+
+        let err = profile("[profiles.odd]\nworld = \"world\"\ntexture_size = 3\n").err();
+        assert_eq!(
+            err.as_deref(),
+            Some("texture_size must be a power of two from 2 to 64")
+        );
+    }
+}
@@ -0,0 +1,584 @@
+//! A builder that runs the whole rendering pipeline, so programs can render a map of a world in a few lines instead of
+//! copying the orchestration of the mcmapper-rs binary.
+//!
+//! A `WorldSource` and the `Textures` loaded from a `TextureSource` are given to a `MapRenderer`, along with the render options,
+//! the part of the world to render and the sinks the map is saved to. Regions are rendered in parallel and saved to the output
+//! folder as they finish, then each sink is written from the saved region images, so memory use does not grow with the size of the world.
+//! Both the world and the textures are borrowed, so several maps can be rendered from them without reading them again.
+
+use crate::area::RenderArea;
+use crate::cache::{read_all_chunk_timestamps, render_fingerprint, RenderCache};
+use crate::dimension::Dimension;
+use crate::error::{McMapperError, Result};
+use crate::isometric::region_to_isometric_image;
+use crate::models::BlockModels;
+use crate::players::{
+    player_map_pixel, read_players, write_players_json, Player, PLAYER_MARKER_COLOR,
+};
+use crate::report::{RegionReport, RenderReport};
+use crate::resources::ResourcePack;
+use crate::shading::{find_region_edge_heights, RegionEdgeHeights};
+use crate::stitch::{
+    save_region_image, stitch_region_image_files, MapOverlay, Marker, RegionImageFile,
+    StitchedImage,
+};
+use crate::surface::SurfaceMode;
+use crate::tiles::{write_tile_pyramid, write_tile_viewer, TilePyramid};
+use crate::world::{
+    locate_world, read_world_info, write_world_json, WorldInfo, SPAWN_MARKER_COLOR,
+};
+use crate::{
+    average_texture_list, get_region_files, get_texture_list, get_texture_list_from_packs,
+    override_block_colors, region_file_to_file_name, region_to_image, ChunkCoordinate, Projection,
+    RegionFile, RegionImage, RenderOptions, TextureListMap,
+};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A dimension of a world to render, along with its region files.
+pub struct WorldSource {
+    /// The world folder, where level.dat and the playerdata folder are read from.
+    pub world_dir: PathBuf,
+    /// The dimension the region files belong to.
+    pub dimension: Dimension,
+    /// The metadata read from level.dat, used to label the map and mark the spawn.
+    pub world_info: Option<WorldInfo>,
+    /// The region files of the dimension, renders of part of the world only read the ones in their area.
+    pub regions: Vec<RegionFile>,
+    /// The heights along the southern edge of each region read so far, for each surface mode they were read with.
+    edge_heights: Mutex<HashMap<SurfaceMode, RegionEdgeHeights>>,
+}
+
+impl WorldSource {
+    /// Creates a world source from a world that has already been read.
+    pub fn new(
+        world_dir: PathBuf,
+        dimension: Dimension,
+        world_info: Option<WorldInfo>,
+        regions: Vec<RegionFile>,
+    ) -> Self {
+        Self {
+            world_dir,
+            dimension,
+            world_info,
+            regions,
+            edge_heights: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads a dimension of a world from a world folder or the region folder inside of one, see `world::locate_world`.
+    pub fn open(path: &Path, dimension: Option<Dimension>) -> Result<Self> {
+        let (world_dir, region_dir, dimension) = locate_world(path, dimension)?;
+        let world_info = read_world_info(&world_dir)?;
+        let regions = get_region_files(&region_dir.to_string_lossy())?;
+        Ok(Self::new(world_dir, dimension, world_info, regions))
+    }
+
+    /// Returns the heights along the southern edge of the given regions, only reading the regions that have not been read with the surface mode before.
+    pub fn edge_heights(&self, list: &[RegionFile], options: &RenderOptions) -> RegionEdgeHeights {
+        let mut edge_heights = self.edge_heights.lock().unwrap();
+        let known = edge_heights.entry(options.surface_mode).or_default();
+        let unknown: Vec<RegionFile> = list
+            .iter()
+            .filter(|region| !known.contains_key(&region.coordinate))
+            .filter_map(|region| region.try_clone().ok())
+            .collect();
+        known.extend(find_region_edge_heights(&unknown, options));
+        let heights = list
+            .iter()
+            .filter_map(|region| Some((region.coordinate, known.get(&region.coordinate)?.clone())))
+            .collect();
+        drop(edge_heights);
+        heights
+    }
+}
+
+/// Where textures are read from and how they are changed, renders with the same source can share their textures.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TextureSource {
+    /// The folder block textures, blockstates and block models are read from when no packs are given.
+    pub assets: PathBuf,
+    /// Client jars, resource pack zips or folders to read textures and block models from, lowest priority first.
+    pub packs: Vec<PathBuf>,
+    /// Replace each texture with its average color, for drawing one pixel per block.
+    pub average: bool,
+    /// Blocks drawn as a solid color instead of their texture, see `override_block_colors`.
+    pub colors: Vec<(String, [u8; 3])>,
+}
+
+impl TextureSource {
+    /// Reads textures from a folder of block textures.
+    pub fn from_assets(assets: impl Into<PathBuf>) -> Self {
+        Self {
+            assets: assets.into(),
+            packs: vec![],
+            average: false,
+            colors: vec![],
+        }
+    }
+
+    /// Reads textures and block models from client jars, resource pack zips or folders, lowest priority first.
+    pub fn from_packs(packs: Vec<PathBuf>) -> Self {
+        Self {
+            packs,
+            ..Self::from_assets("assets")
+        }
+    }
+
+    /// Reads the textures and block models.
+    pub fn load(&self) -> Result<Textures> {
+        let packs: Vec<ResourcePack> = self.packs.iter().map(ResourcePack::new).collect();
+        let texture_list = if packs.is_empty() {
+            get_texture_list(&self.assets)?
+        } else {
+            get_texture_list_from_packs(&packs)?
+        };
+        let mut texture_list = if self.average {
+            average_texture_list(&texture_list)
+        } else {
+            texture_list
+        };
+        override_block_colors(&mut texture_list, &self.colors);
+        // blocks without a blockstate, e.g. when the assets folder has none, are matched to textures by name.
+        let block_models = if packs.is_empty() {
+            BlockModels::from_assets(&self.assets)?
+        } else {
+            BlockModels::from_packs(&packs)?
+        };
+        Ok(Textures {
+            texture_list,
+            block_models,
+        })
+    }
+}
+
+/// The textures and block models a map is drawn with.
+pub struct Textures {
+    /// The block textures keyed by their name.
+    pub texture_list: TextureListMap,
+    /// The block models used to pick the top face texture of each block.
+    pub block_models: BlockModels,
+}
+
+/// Something a rendered map is saved as, inside of the output folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapSink {
+    /// A single stitched image, cropped to what was rendered.
+    Image {
+        /// The file name of the image, e.g. map.png.
+        file_name: String,
+        /// How many times smaller than full size the image is, 1 for full size.
+        downscale: u32,
+    },
+    /// A z/x/y tile pyramid along with an index.html viewer for it.
+    Tiles {
+        /// The name of the folder the tiles are written to.
+        folder_name: String,
+    },
+}
+
+/// What a render is doing, given to the progress hook as it happens.
+#[derive(Debug, Clone)]
+pub enum RenderProgress {
+    /// The regions in the area have been found and are about to be rendered.
+    RegionsFound {
+        /// The number of regions that will be rendered.
+        count: usize,
+    },
+    /// A region is in the render cache, and only the chunks that changed are drawn again.
+    RegionCached {
+        /// The coordinate of the region.
+        coordinate: ChunkCoordinate,
+        /// The number of chunks drawn again.
+        dirty_chunks: usize,
+    },
+    /// A region has been rendered and saved, regions finish in no particular order.
+    RegionRendered {
+        /// The coordinate of the region.
+        coordinate: ChunkCoordinate,
+        /// The number of regions rendered so far, including this one.
+        done: u32,
+        /// The number of regions being rendered.
+        total: u32,
+    },
+    /// The players in the rendered dimension have been read, to be marked on the map.
+    PlayersFound {
+        /// The number of players.
+        count: usize,
+    },
+    /// A sink is about to be written.
+    Saving {
+        /// The image or tile folder being written.
+        path: PathBuf,
+    },
+    /// Some of the region images of a stitched image have been read and written into it.
+    Stitching {
+        /// The image being written.
+        path: PathBuf,
+        /// The number of region images read so far.
+        done: usize,
+        /// The number of region images in the image.
+        total: usize,
+    },
+    /// A zoom level of a tile pyramid has been written, levels are written from the most detailed one down to zoom level 0.
+    ZoomLevelWritten {
+        /// The tile folder being written.
+        path: PathBuf,
+        /// The zoom level that was written.
+        zoom: u32,
+        /// The number of tiles in the zoom level.
+        tiles: usize,
+    },
+    /// A sink has been written.
+    Saved {
+        /// The image or tile folder that was written.
+        path: PathBuf,
+        /// How long it took to write.
+        elapsed: Duration,
+    },
+}
+
+/// The function progress is reported to, it is called from the threads regions are rendered on.
+pub type ProgressHook<'a> = Box<dyn Fn(&RenderProgress) + Send + Sync + 'a>;
+
+/// The result of a finished render.
+#[derive(Debug, Clone)]
+pub struct RenderedMap {
+    /// What happened to every chunk, also saved to render-report.json in the output folder.
+    pub report: RenderReport,
+    /// The region images saved in the output folder.
+    pub region_images: Vec<RegionImageFile>,
+    /// The players marked on the map, empty unless players were asked for.
+    pub players: Vec<Player>,
+    /// The stitched images that were saved, with where they sit on the map, see `StitchedImage::block_at`.
+    /// Images are left out if nothing was rendered.
+    pub images: Vec<(PathBuf, StitchedImage)>,
+    /// The tile pyramids that were written, keyed by their folder.
+    pub tiles: Vec<(PathBuf, TilePyramid)>,
+}
+
+/// Renders a world to images, see the module documentation for how the pieces fit together.
+pub struct MapRenderer<'a> {
+    world: &'a WorldSource,
+    textures: &'a Textures,
+    options: RenderOptions,
+    area: Option<RenderArea>,
+    output_dir: PathBuf,
+    sinks: Vec<MapSink>,
+    players: bool,
+    full_render: bool,
+    threads: Option<usize>,
+    progress: Option<ProgressHook<'a>>,
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+impl<'a> MapRenderer<'a> {
+    /// Creates a renderer for a world and the textures to draw it with.
+    ///
+    /// By default the whole world is rendered top down with the default render options into a folder named output,
+    /// and only the region images are saved until sinks are added.
+    pub fn new(world: &'a WorldSource, textures: &'a Textures) -> Self {
+        Self {
+            world,
+            textures,
+            options: RenderOptions::default(),
+            area: None,
+            output_dir: PathBuf::from("output"),
+            sinks: vec![],
+            players: false,
+            full_render: false,
+            threads: None,
+            progress: None,
+            cancel: None,
+        }
+    }
+
+    /// Sets how the world is drawn.
+    pub const fn options(mut self, options: RenderOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Only renders part of the world.
+    pub fn area(mut self, area: RenderArea) -> Self {
+        self.area = Some(area);
+        self
+    }
+
+    /// Sets the folder region images, the render cache, the report and the sinks are saved in.
+    pub fn output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = output_dir.into();
+        self
+    }
+
+    /// Adds something to save the map as, can be called more than once.
+    pub fn sink(mut self, sink: MapSink) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Marks the last known position of every player in the rendered dimension, and saves them to players.json.
+    pub const fn players(mut self, players: bool) -> Self {
+        self.players = players;
+        self
+    }
+
+    /// Ignores the render cache and draws every chunk again.
+    pub const fn full_render(mut self, full_render: bool) -> Self {
+        self.full_render = full_render;
+        self
+    }
+
+    /// Limits the number of regions rendered at once, by default one for each cpu core.
+    pub const fn threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Calls a function as the render makes progress.
+    pub fn on_progress(mut self, hook: impl Fn(&RenderProgress) + Send + Sync + 'a) -> Self {
+        self.progress = Some(Box::new(hook));
+        self
+    }
+
+    /// Stops the render with `McMapperError::Cancelled` once the flag is set, checked before each region and sink.
+    pub fn cancel_flag(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Renders the map and saves it to every sink, with its own thread pool if the number of threads is limited.
+    pub fn render(&self) -> Result<RenderedMap> {
+        match self.threads {
+            None => self.render_in_pool(),
+            Some(threads) => rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(McMapperError::ThreadPool)?
+                .install(|| self.render_in_pool()),
+        }
+    }
+
+    fn report(&self, progress: RenderProgress) {
+        if let Some(hook) = &self.progress {
+            hook(&progress);
+        }
+    }
+
+    fn check_cancelled(&self) -> Result<()> {
+        match &self.cancel {
+            Some(cancel) if cancel.load(Ordering::Relaxed) => Err(McMapperError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    fn render_in_pool(&self) -> Result<RenderedMap> {
+        let options = &self.options;
+        let output_dir = &self.output_dir;
+        let (texture_list, block_models) =
+            (&self.textures.texture_list, &self.textures.block_models);
+        fs::create_dir_all(output_dir).map_err(|err| McMapperError::io(output_dir, err))?;
+
+        let mut list: Vec<RegionFile> = vec![];
+        for region in &self.world.regions {
+            let region = region.try_clone()?;
+            match &self.area {
+                None => list.push(region),
+                Some(area) => list.extend(area.select(region)),
+            }
+        }
+        if list.is_empty() && self.area.is_some() {
+            return Err(McMapperError::NoRegionsInArea);
+        }
+        self.report(RenderProgress::RegionsFound { count: list.len() });
+
+        let edge_heights = match options.projection {
+            Projection::TopDown => self.world.edge_heights(&list, options), // heights along the border of each region, so shading lines up between regions
+            Projection::Isometric => Default::default(), // isometric images shade the faces of each cube instead
+        };
+
+        // chunks that have not been saved since the last run are copied from the last render instead of being drawn again.
+        let fingerprint = render_fingerprint(texture_list, block_models, options);
+        let chunk_timestamps = read_all_chunk_timestamps(&list);
+        let mut render_cache = if self.full_render {
+            RenderCache::new(&fingerprint, &Default::default())
+        } else {
+            RenderCache::load(output_dir, &fingerprint)
+        };
+
+        let region_images: Mutex<Vec<RegionImageFile>> = Mutex::new(vec![]); // the images themselves are dropped once saved
+        let region_reports: Mutex<Vec<RegionReport>> = Mutex::new(vec![]);
+        let regions_finished = AtomicU32::new(0);
+        let number_of_regions = list.len() as u32;
+
+        list.into_par_iter().try_for_each(|region| -> Result<()> {
+            self.check_cancelled()?;
+            let image_path = output_dir.join(region_file_to_file_name(&region));
+            // isometric region images overlap the chunks of their neighbours, so they are always drawn in full.
+            let cached = match options.projection {
+                Projection::TopDown => render_cache.cached_region(
+                    &region.coordinate,
+                    &chunk_timestamps,
+                    &image_path,
+                    options,
+                ),
+                Projection::Isometric => None,
+            };
+            if let Some(c) = &cached {
+                self.report(RenderProgress::RegionCached {
+                    coordinate: region.coordinate,
+                    dirty_chunks: c.dirty.iter().filter(|dirty| **dirty).count(),
+                });
+            }
+            let (region_image, report) = match options.projection {
+                Projection::TopDown => region_to_image(
+                    &region,
+                    texture_list,
+                    block_models,
+                    &edge_heights,
+                    options,
+                    cached,
+                )?,
+                Projection::Isometric => {
+                    region_to_isometric_image(&region, texture_list, block_models, options)?
+                }
+            };
+
+            // the region image is saved and freed right away, so memory does not grow with the size of the world
+            let saved_region = save_region_image(
+                RegionImage {
+                    coordinate: region.coordinate,
+                    origin: options.region_origin(&region.coordinate),
+                    image: region_image,
+                },
+                &image_path,
+            )?;
+            region_images.lock().unwrap().push(saved_region);
+            region_reports.lock().unwrap().push(report);
+            self.report(RenderProgress::RegionRendered {
+                coordinate: region.coordinate,
+                done: regions_finished.fetch_add(1, Ordering::Relaxed) + 1,
+                total: number_of_regions,
+            });
+            Ok(())
+        })?;
+
+        let region_images = region_images.into_inner().unwrap();
+        let report = RenderReport::new(region_reports.into_inner().unwrap());
+        report.save(output_dir)?;
+        render_cache.update(&chunk_timestamps); // regions outside of the area stay cached for later runs
+        render_cache.forget_failures(&report); // chunks that could not be read are tried again next run
+        render_cache.save(output_dir)?;
+
+        let mut overlay = MapOverlay::default();
+        // the spawn point is in the overworld, so it is only marked on maps of it.
+        if let Some(world_info) = &self.world.world_info {
+            overlay
+                .labels
+                .push(("Title".to_string(), world_info.name.clone()));
+            overlay
+                .labels
+                .push(("Description".to_string(), world_info.description()));
+            if self.world.dimension == Dimension::Overworld {
+                overlay.markers.push(Marker {
+                    pixel: world_info.spawn_map_pixel(options),
+                    color: SPAWN_MARKER_COLOR,
+                });
+            }
+            write_world_json(
+                &output_dir.join("world.json"),
+                world_info,
+                &self.world.dimension,
+                options,
+            )?;
+        }
+
+        let players: Vec<Player> = if self.players {
+            let players: Vec<Player> = read_players(&self.world.world_dir)?
+                .into_iter()
+                .filter(|player| player.dimension == self.world.dimension)
+                .collect();
+            self.report(RenderProgress::PlayersFound {
+                count: players.len(),
+            });
+            write_players_json(&output_dir.join("players.json"), &players, options)?;
+            overlay.markers.extend(players.iter().map(|player| Marker {
+                pixel: player_map_pixel(player, options),
+                color: PLAYER_MARKER_COLOR,
+            }));
+            players
+        } else {
+            vec![]
+        };
+
+        let mut images = vec![];
+        let mut tiles = vec![];
+        for sink in &self.sinks {
+            self.check_cancelled()?;
+            let start = Instant::now();
+            match sink {
+                MapSink::Image {
+                    file_name,
+                    downscale,
+                } => {
+                    let path = output_dir.join(file_name);
+                    self.report(RenderProgress::Saving { path: path.clone() });
+                    // the stitched image is streamed to disk already cropped and scaled, so the full map is never held in memory.
+                    let stitched = stitch_region_image_files(
+                        &region_images,
+                        &path,
+                        *downscale,
+                        &overlay,
+                        |done, total| {
+                            self.report(RenderProgress::Stitching {
+                                path: path.clone(),
+                                done,
+                                total,
+                            })
+                        },
+                    )?;
+                    if let Some(image) = stitched {
+                        images.push((path.clone(), image));
+                    }
+                    self.report(RenderProgress::Saved {
+                        path,
+                        elapsed: start.elapsed(),
+                    });
+                }
+                MapSink::Tiles { folder_name } => {
+                    let tile_dir = output_dir.join(folder_name);
+                    self.report(RenderProgress::Saving {
+                        path: tile_dir.clone(),
+                    });
+                    let pyramid =
+                        write_tile_pyramid(&region_images, &tile_dir, options, |zoom, tiles| {
+                            self.report(RenderProgress::ZoomLevelWritten {
+                                path: tile_dir.clone(),
+                                zoom,
+                                tiles,
+                            })
+                        })?;
+                    write_tile_viewer(&tile_dir, &pyramid)?;
+                    tiles.push((tile_dir.clone(), pyramid));
+                    self.report(RenderProgress::Saved {
+                        path: tile_dir,
+                        elapsed: start.elapsed(),
+                    });
+                }
+            }
+        }
+
+        Ok(RenderedMap {
+            report,
+            region_images,
+            players,
+            images,
+            tiles,
+        })
+    }
+}
@@ -0,0 +1,177 @@
+//! A report of the chunks of every region that could not be drawn, saved next to the render so corruption in a world can be found before players run into it.
+
+use crate::chunk::{ChunkError, ChunkProblem};
+use crate::error::{McMapperError, Result};
+use crate::ChunkCoordinate;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// The name of the report file saved in the output folder.
+pub const REPORT_FILE_NAME: &str = "render-report.json";
+
+/// The color chunks that could not be read are filled with when rendering with an error overlay, a color no block has.
+pub const ERROR_OVERLAY_COLOR: [u8; 3] = [255, 0, 255];
+
+/// The number of chunks that were drawn, and that could not be drawn for each kind of problem.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ChunkCounts {
+    /// Chunks that were read and drawn.
+    pub rendered: u32,
+    /// Chunks that had not changed since the last render and were copied from it without being read.
+    pub cached: u32,
+    /// Chunks that have not been generated yet.
+    pub not_generated: u32,
+    /// Chunks that could not be read from their region file.
+    pub io_error: u32,
+    /// Chunks whose data could not be decompressed.
+    pub compression_error: u32,
+    /// Chunks whose data is not valid nbt.
+    pub nbt_error: u32,
+    /// Chunks that are valid nbt but not in a chunk format that can be read.
+    pub unsupported_format: u32,
+}
+
+impl ChunkCounts {
+    /// The number of chunks that exist but could not be drawn, not counting chunks that are not generated.
+    pub const fn failed(&self) -> u32 {
+        self.io_error + self.compression_error + self.nbt_error + self.unsupported_format
+    }
+
+    const fn add(&mut self, other: &Self) {
+        self.rendered += other.rendered;
+        self.cached += other.cached;
+        self.not_generated += other.not_generated;
+        self.io_error += other.io_error;
+        self.compression_error += other.compression_error;
+        self.nbt_error += other.nbt_error;
+        self.unsupported_format += other.unsupported_format;
+    }
+}
+
+/// A chunk that exists but could not be drawn.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChunkFailure {
+    /// The x and z coordinate of the chunk in the world, which is its block coordinate divided by 16.
+    pub chunk: (i32, i32),
+    /// What kind of problem the chunk has.
+    pub problem: ChunkProblem,
+    /// A description of the problem.
+    pub message: String,
+}
+
+/// What happened to each chunk of a region when it was rendered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RegionReport {
+    /// The coordinate of the region, e.g. 0,-1 for r.0.-1.mca.
+    pub region: (i32, i32),
+    /// The number of chunks of the region that were drawn, and that could not be drawn for each kind of problem.
+    pub chunks: ChunkCounts,
+    /// Every chunk of the region that exists but could not be drawn.
+    pub failures: Vec<ChunkFailure>,
+    /// Why the region file could not be read at all, e.g. because it is empty or cut short before its header ends.
+    /// None of its chunks are counted when it is set.
+    pub region_error: Option<String>,
+}
+
+impl RegionReport {
+    /// An empty report for a region, with nothing counted yet.
+    pub const fn new(coordinate: &ChunkCoordinate) -> Self {
+        Self {
+            region: (coordinate.0, coordinate.1),
+            chunks: ChunkCounts {
+                rendered: 0,
+                cached: 0,
+                not_generated: 0,
+                io_error: 0,
+                compression_error: 0,
+                nbt_error: 0,
+                unsupported_format: 0,
+            },
+            failures: vec![],
+            region_error: None,
+        }
+    }
+
+    /// Records that the region file could not be read, so none of its chunks could be drawn.
+    pub fn region_failed(&mut self, error: &McMapperError) {
+        self.region_error = Some(error.to_string());
+    }
+
+    /// Counts a chunk that was drawn.
+    pub const fn rendered(&mut self) {
+        self.chunks.rendered += 1;
+    }
+
+    /// Counts a chunk that was copied from the last render.
+    pub const fn cached(&mut self) {
+        self.chunks.cached += 1;
+    }
+
+    /// Counts a chunk that could not be drawn, given by its x and z inside of the region.
+    pub fn failed(&mut self, chunk_x: usize, chunk_z: usize, error: &ChunkError) {
+        let count = match error.problem {
+            ChunkProblem::NotGenerated => {
+                self.chunks.not_generated += 1;
+                return; // missing chunks are normal at the edges of a world, so they are only counted
+            }
+            ChunkProblem::IoError => &mut self.chunks.io_error,
+            ChunkProblem::CompressionError => &mut self.chunks.compression_error,
+            ChunkProblem::NbtError => &mut self.chunks.nbt_error,
+            ChunkProblem::UnsupportedFormat => &mut self.chunks.unsupported_format,
+        };
+        *count += 1;
+        self.failures.push(ChunkFailure {
+            chunk: (
+                self.region.0 * 32 + chunk_x as i32,
+                self.region.1 * 32 + chunk_z as i32,
+            ),
+            problem: error.problem,
+            message: error.message.clone(),
+        });
+    }
+}
+
+/// What happened to every chunk of a render, saved to render-report.json in the output folder.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RenderReport {
+    /// The chunk counts of every region added together.
+    pub chunks: ChunkCounts,
+    /// The number of region files that could not be read at all.
+    pub unreadable_regions: u32,
+    /// The report of each region, in order of their coordinates.
+    pub regions: Vec<RegionReport>,
+}
+
+impl RenderReport {
+    /// Collects the reports of every rendered region.
+    pub fn new(mut regions: Vec<RegionReport>) -> Self {
+        regions.sort_by_key(|report| report.region);
+        let mut chunks = ChunkCounts::default();
+        for report in &regions {
+            chunks.add(&report.chunks);
+        }
+        let unreadable_regions = regions
+            .iter()
+            .filter(|report| report.region_error.is_some())
+            .count() as u32;
+        Self {
+            chunks,
+            unreadable_regions,
+            regions,
+        }
+    }
+
+    /// Returns true if any region or chunk that exists could not be drawn.
+    pub const fn has_failures(&self) -> bool {
+        self.unreadable_regions > 0 || self.chunks.failed() > 0
+    }
+
+    /// Saves the report to the output folder.
+    pub fn save(&self, output_dir: &Path) -> Result<()> {
+        let path = output_dir.join(REPORT_FILE_NAME);
+        let json =
+            serde_json::to_string_pretty(self).map_err(|err| McMapperError::json(&path, err))?;
+        fs::write(&path, json).map_err(|err| McMapperError::io(&path, err))
+    }
+}
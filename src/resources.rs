@@ -0,0 +1,98 @@
+//! Resource packs that textures are read from, like the minecraft client jar, a resource pack zip, or an extracted resource pack folder.
+//!
+//! Packs are stacked the same way minecraft stacks them, a file in a pack replaces the same file in every pack below it.
+//! Here the packs are given lowest priority first, so the client jar comes first and the server resource pack after it.
+
+use crate::error::{McMapperError, Result};
+use std::fs;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// A resource pack, either a zip file (client jars are zip files too) or a folder that holds an assets folder.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourcePack {
+    /// The path to the zip file or folder of the pack.
+    pub path: PathBuf,
+}
+
+impl ResourcePack {
+    /// Creates a resource pack from the path of a zip file, jar file, or folder.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Reads every file in the pack whose path matches the filter, the paths are relative to the root of the pack and use '/', e.g. "assets/minecraft/textures/block/stone.png".
+    pub fn read_files(&self, filter: impl Fn(&str) -> bool) -> Result<Vec<(String, Vec<u8>)>> {
+        if self.path.is_dir() {
+            let mut files = vec![];
+            read_folder_files(&self.path, &self.path.join("assets"), &filter, &mut files)?;
+            return Ok(files);
+        }
+
+        let file = File::open(&self.path).map_err(|err| McMapperError::io(&self.path, err))?;
+        let archive_error = |source| McMapperError::Archive {
+            path: self.path.clone(),
+            source,
+        };
+        let mut archive = ZipArchive::new(file).map_err(archive_error)?;
+        let mut files = vec![];
+        for index in 0..archive.len() {
+            let mut entry = archive.by_index(index).map_err(archive_error)?;
+            if !entry.is_file() || !filter(entry.name()) {
+                continue;
+            }
+            let name = entry.name().to_string();
+            let mut bytes = Vec::with_capacity(entry.size() as usize);
+            entry
+                .read_to_end(&mut bytes)
+                .map_err(|err| McMapperError::io(&self.path.join(&name), err))?;
+            files.push((name, bytes));
+        }
+        Ok(files)
+    }
+}
+
+/// Recursively reads the files in a folder pack that match the filter, with paths relative to the root of the pack.
+fn read_folder_files(
+    root: &Path,
+    dir: &Path,
+    filter: &impl Fn(&str) -> bool,
+    files: &mut Vec<(String, Vec<u8>)>,
+) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(()); // a pack without an assets folder just has nothing in it
+    }
+    for entry in fs::read_dir(dir).map_err(|err| McMapperError::io(dir, err))? {
+        let path = entry.map_err(|err| McMapperError::io(dir, err))?.path();
+        if path.is_dir() {
+            read_folder_files(root, &path, filter, files)?;
+            continue;
+        }
+        let relative = match path.strip_prefix(root) {
+            Ok(relative) => relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/"),
+            Err(_) => continue,
+        };
+        if filter(&relative) {
+            let bytes = fs::read(&path).map_err(|err| McMapperError::io(&path, err))?;
+            files.push((relative, bytes));
+        }
+    }
+    Ok(())
+}
+
+/// Returns the texture name of a block texture inside of a pack, e.g. "assets/minecraft/textures/block/stone.png" becomes "minecraft:stone".
+/// Returns none if the path is not a block texture.
+pub fn block_texture_name(path: &str) -> Option<String> {
+    match path.split('/').collect::<Vec<&str>>().as_slice() {
+        ["assets", namespace, "textures", "block", file_name] => file_name
+            .strip_suffix(".png")
+            .map(|name| format!("{}:{}", namespace, name)),
+        _ => None,
+    }
+}
@@ -0,0 +1,218 @@
+//! Relief shading of the flattened map, so that height differences between neighbouring blocks can be seen.
+//!
+//! This works the same way as vanilla map items, each block is compared to the block north of it,
+//! blocks that are higher than their northern neighbour are brightened and blocks that are lower are darkened.
+
+use crate::surface::{load_chunk_surface, SurfaceMode};
+use crate::{ChunkCoordinate, RegionFile, RenderOptions};
+use image::{Rgb, RgbImage};
+use rayon::prelude::*;
+use std::collections::HashMap;
+
+/// Number of blocks along one side of a region.
+pub const REGION_BLOCK_WIDTH: usize = 512;
+
+/// Surface heights of every column in a region, indexed by z * 512 + x, none if the column has no blocks or the chunk is missing.
+pub type RegionHeights = Vec<Option<isize>>;
+
+/// The surface heights of the southern most row of blocks in each region, keyed by the coordinate of the region.
+/// Used to shade the northern most row of the region directly south of it.
+pub type RegionEdgeHeights = HashMap<ChunkCoordinate, Vec<Option<isize>>>;
+
+/// Brightness multiplier for a block that is higher than the block north of it, matches the ratio vanilla maps use between their bright and normal shades.
+const BRIGHT_MULTIPLIER: f32 = 255.0 / 220.0;
+/// Brightness multiplier for a block that is lower than the block north of it, matches the ratio vanilla maps use between their dark and normal shades.
+const DARK_MULTIPLIER: f32 = 180.0 / 220.0;
+
+/// Opacity of a translucent block drawn over the block under it when there is a single block of it, like a shallow coast.
+const SHALLOW_OPACITY: f32 = 0.45;
+/// Opacity added for each extra block of depth, so deep oceans are darker than shallow water.
+const OPACITY_PER_BLOCK: f32 = 0.05;
+/// The highest opacity a translucent block reaches, so even the deepest oceans are not completely flat.
+const MAX_OPACITY: f32 = 0.9;
+
+/// Returns how opaque translucent blocks like water are drawn, given how many blocks deep they are above the block under them.
+pub fn translucent_opacity(depth: isize) -> f32 {
+    ((depth - 1).max(0) as f32)
+        .mul_add(OPACITY_PER_BLOCK, SHALLOW_OPACITY)
+        .min(MAX_OPACITY)
+}
+
+/// Blends a color over another one with the given opacity.
+pub fn blend(under: Rgb<u8>, over: Rgb<u8>, opacity: f32) -> Rgb<u8> {
+    Rgb([0, 1, 2].map(|i| {
+        (under.0[i] as f32)
+            .mul_add(1.0 - opacity, over.0[i] as f32 * opacity)
+            .round() as u8
+    }))
+}
+
+/// Returns how much a block should be brightened or darkened, given its height and the height of the block north of it.
+pub const fn shade_multiplier(height: isize, north_height: Option<isize>) -> f32 {
+    match north_height {
+        Some(north) if height > north => BRIGHT_MULTIPLIER,
+        Some(north) if height < north => DARK_MULTIPLIER,
+        _ => 1.0,
+    }
+}
+
+/// Applies relief shading to a rendered region image, where each block is drawn as a square of block_pixels by block_pixels.
+///
+/// The north edge is the row of heights directly north of this region, taken from the region north of it, if it exists.
+pub fn apply_hillshading(
+    image: &mut RgbImage,
+    heights: &RegionHeights,
+    north_edge: Option<&Vec<Option<isize>>>,
+    block_pixels: u32,
+) {
+    apply_hillshading_to_chunks(image, heights, north_edge, block_pixels, &[true; 32 * 32]);
+}
+
+/// Applies relief shading only to the chunks marked in the chunk mask, indexed by z * 32 + x.
+///
+/// Used when only some chunks were rendered and the rest were already shaded, heights of unmasked chunks are still used for comparisons.
+pub fn apply_hillshading_to_chunks(
+    image: &mut RgbImage,
+    heights: &RegionHeights,
+    north_edge: Option<&Vec<Option<isize>>>,
+    block_pixels: u32,
+    chunk_mask: &[bool],
+) {
+    for z in 0..REGION_BLOCK_WIDTH {
+        for x in 0..REGION_BLOCK_WIDTH {
+            if !chunk_mask[(z / 16) * 32 + x / 16] {
+                continue;
+            }
+            let height = match heights[z * REGION_BLOCK_WIDTH + x] {
+                None => continue,
+                Some(h) => h,
+            };
+
+            let north_height = if z == 0 {
+                north_edge.and_then(|edge| edge[x])
+            } else {
+                heights[(z - 1) * REGION_BLOCK_WIDTH + x]
+            };
+
+            let multiplier = shade_multiplier(height, north_height);
+            if (multiplier - 1.0).abs() < f32::EPSILON {
+                continue; // flat ground is left alone
+            }
+
+            for pixel_y in (z as u32 * block_pixels)..((z as u32 + 1) * block_pixels) {
+                for pixel_x in (x as u32 * block_pixels)..((x as u32 + 1) * block_pixels) {
+                    let pixel = image.get_pixel_mut(pixel_x, pixel_y);
+                    for channel in pixel.0.iter_mut() {
+                        *channel = (*channel as f32 * multiplier).round().clamp(0.0, 255.0) as u8;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Reads the southern most row of chunks of every region given, and records the surface height of the last row of blocks.
+///
+/// This is much cheaper than rendering the regions, and lets each region be shaded correctly along its northern border.
+pub fn find_region_edge_heights(list: &[RegionFile], options: &RenderOptions) -> RegionEdgeHeights {
+    list.par_iter()
+        .map(|region| {
+            (
+                region.coordinate,
+                region_south_edge_heights(region, options.surface_mode),
+            )
+        })
+        .collect()
+}
+
+/// Returns the surface heights of the southern most row of blocks in a region, from west to east.
+fn region_south_edge_heights(
+    region_selected: &RegionFile,
+    surface_mode: SurfaceMode,
+) -> Vec<Option<isize>> {
+    let mut edge = vec![None; REGION_BLOCK_WIDTH];
+
+    let mut region = match fastanvil::Region::from_stream(&region_selected.file) {
+        Ok(r) => r,
+        Err(_) => return edge, // an unreadable region has no heights to share
+    };
+
+    for chunk_x in 0..32 {
+        if let Ok((chunk, surface)) = load_chunk_surface(&mut region, chunk_x, 31) {
+            for x in 0..16 {
+                edge[chunk_x * 16 + x] = surface
+                    .column_block(&chunk, x, 15, surface_mode)
+                    .map(|(_, y)| y);
+            }
+        }
+    }
+
+    edge
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+
+    #[test]
+    fn higher_blocks_are_brightened_and_lower_blocks_darkened() {
+        assert_eq!(shade_multiplier(65, Some(64)), BRIGHT_MULTIPLIER);
+        assert_eq!(shade_multiplier(63, Some(64)), DARK_MULTIPLIER);
+        assert_eq!(shade_multiplier(64, Some(64)), 1.0);
+        assert_eq!(shade_multiplier(64, None), 1.0);
+    }
+
+    #[test]
+    fn the_north_edge_shades_the_first_row() {
+        let mut image = RgbImage::from_pixel(512, 512, Rgb([110, 110, 110]));
+        let mut heights = vec![None; REGION_BLOCK_WIDTH * REGION_BLOCK_WIDTH];
+        heights[0] = Some(70); // higher than the region north of it
+        heights[1] = Some(60); // lower than the region north of it
+        heights[REGION_BLOCK_WIDTH] = Some(70); // level with the block north of it
+        heights[REGION_BLOCK_WIDTH + 1] = Some(70); // higher than the block north of it
+        let mut north_edge = vec![None; REGION_BLOCK_WIDTH];
+        north_edge[0] = Some(64);
+        north_edge[1] = Some(64);
+
+        apply_hillshading(&mut image, &heights, Some(&north_edge), 1);
+
+        assert_eq!(image.get_pixel(0, 0), &Rgb([128, 128, 128]));
+        assert_eq!(image.get_pixel(1, 0), &Rgb([90, 90, 90]));
+        assert_eq!(image.get_pixel(0, 1), &Rgb([110, 110, 110]));
+        assert_eq!(image.get_pixel(1, 1), &Rgb([128, 128, 128]));
+        assert_eq!(image.get_pixel(2, 0), &Rgb([110, 110, 110]));
+    }
+
+    #[test]
+    fn blocks_are_shaded_across_their_whole_square() {
+        let mut image = RgbImage::from_pixel(1024, 1024, Rgb([110, 110, 110]));
+        let mut heights = vec![None; REGION_BLOCK_WIDTH * REGION_BLOCK_WIDTH];
+        heights[REGION_BLOCK_WIDTH] = Some(60);
+        heights[0] = Some(64);
+
+        apply_hillshading(&mut image, &heights, None, 2);
+
+        for (x, y) in [(0, 2), (1, 2), (0, 3), (1, 3)] {
+            assert_eq!(image.get_pixel(x, y), &Rgb([90, 90, 90]));
+        }
+        assert_eq!(image.get_pixel(0, 1), &Rgb([110, 110, 110]));
+        assert_eq!(image.get_pixel(2, 2), &Rgb([110, 110, 110]));
+    }
+
+    #[test]
+    fn deeper_water_is_more_opaque() {
+        assert_eq!(translucent_opacity(1), SHALLOW_OPACITY);
+        assert!(translucent_opacity(5) > translucent_opacity(2));
+        assert_eq!(translucent_opacity(200), MAX_OPACITY);
+    }
+
+    #[test]
+    fn translucent_colors_are_blended_over_the_block_under_them() {
+        let under = Rgb([0, 0, 0]);
+        let over = Rgb([200, 100, 50]);
+        assert_eq!(blend(under, over, 0.5), Rgb([100, 50, 25]));
+        assert_eq!(blend(under, over, 1.0), over);
+        assert_eq!(blend(under, over, 0.0), under);
+    }
+}
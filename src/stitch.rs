@@ -0,0 +1,443 @@
+//! Stitching of region images that have already been saved to disk, without ever holding the whole world in memory.
+//!
+//! Each region image is saved and dropped as soon as it is rendered, then the stitched image is written one row of pixels at a time.
+//! Only one row of pixels from each region that overlaps the current row is held in memory, so memory use depends on the width of the world and not its area.
+//! Region images are placed by their origin and drawn from top to bottom, leaving out black pixels, so overlapping isometric region images are layered correctly.
+
+use crate::coordinate::{BlockPos, PixelPos};
+use crate::error::{McMapperError, Result};
+use crate::{ChunkCoordinate, RegionImage, RenderOptions};
+use image::RgbImage;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// The area of an image that contains anything other than black pixels, as x and y to start, and x and y to end exclusively.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ContentBounds {
+    /// The left most column that contains a non black pixel.
+    pub min_x: u32,
+    /// The top most row that contains a non black pixel.
+    pub min_y: u32,
+    /// One past the right most column that contains a non black pixel.
+    pub max_x: u32,
+    /// One past the bottom most row that contains a non black pixel.
+    pub max_y: u32,
+}
+
+/// A region image that has been saved to disk, along with what is needed to place it in a stitched image.
+#[derive(Debug, Clone)]
+pub struct RegionImageFile {
+    /// The coordinate of the region the image was rendered from.
+    pub coordinate: ChunkCoordinate,
+    /// The position of the top left pixel of the image in the stitched map, see `RenderOptions::region_origin`.
+    pub origin: (i64, i64),
+    /// The path the region image was saved to.
+    pub path: PathBuf,
+    /// The width of the region image in pixels.
+    pub width: u32,
+    /// The height of the region image in pixels.
+    pub height: u32,
+    /// The area of the region image that has anything rendered in it, none if the region image is completely black.
+    pub bounds: Option<ContentBounds>,
+}
+
+/// Finds the area of an image that contains anything other than black pixels, or none if the image is completely black.
+pub fn find_content_bounds(image: &RgbImage) -> Option<ContentBounds> {
+    let mut bounds: Option<ContentBounds> = None;
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel.0 == [0, 0, 0] {
+            continue;
+        }
+        bounds = Some(match bounds {
+            None => ContentBounds {
+                min_x: x,
+                min_y: y,
+                max_x: x + 1,
+                max_y: y + 1,
+            },
+            Some(b) => ContentBounds {
+                min_x: b.min_x.min(x),
+                min_y: b.min_y.min(y),
+                max_x: b.max_x.max(x + 1),
+                max_y: b.max_y.max(y + 1),
+            },
+        });
+    }
+    bounds
+}
+
+/// Saves a region image to the given path and drops it, returning what is needed to stitch it later.
+pub fn save_region_image(region_image: RegionImage, path: &Path) -> Result<RegionImageFile> {
+    let bounds = find_content_bounds(&region_image.image);
+    region_image
+        .image
+        .save(path)
+        .map_err(|err| McMapperError::image(path, err))?;
+    Ok(RegionImageFile {
+        coordinate: region_image.coordinate,
+        origin: region_image.origin,
+        path: path.to_path_buf(),
+        width: region_image.image.width(),
+        height: region_image.image.height(),
+        bounds,
+    })
+}
+
+/// Where a stitched image sits on the map, used to find the pixel of the image a block is drawn at.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct StitchedImage {
+    /// The width of the stitched image in pixels.
+    pub width: u32,
+    /// The height of the stitched image in pixels.
+    pub height: u32,
+    /// The full resolution pixel of the map, relative to block 0,0, that the top left pixel of the image shows.
+    pub origin: (i64, i64),
+    /// How many full resolution pixels each pixel of the image covers on each axis.
+    pub downscale: u32,
+}
+
+impl StitchedImage {
+    /// Returns the pixel of the image that shows a full resolution pixel of the map, see `RenderOptions::map_pixel`.
+    /// Returns none if the pixel was cropped out of the image.
+    pub const fn image_pixel(&self, map_pixel: (i64, i64)) -> Option<(u32, u32)> {
+        let x = (map_pixel.0 - self.origin.0).div_euclid(self.downscale as i64);
+        let y = (map_pixel.1 - self.origin.1).div_euclid(self.downscale as i64);
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return None;
+        }
+        Some((x as u32, y as u32))
+    }
+
+    /// Returns the full resolution pixel of the map in the middle of the ones a pixel of the image covers, the reverse of `image_pixel`.
+    pub const fn map_pixel(&self, image_pixel: (u32, u32)) -> PixelPos {
+        let downscale = self.downscale as i64;
+        PixelPos::new(
+            self.origin.0 + image_pixel.0 as i64 * downscale + downscale / 2,
+            self.origin.1 + image_pixel.1 as i64 * downscale + downscale / 2,
+        )
+    }
+
+    /// Returns the block shown at a pixel of the image, see `PixelPos::block` for what the y level is used for.
+    pub fn block_at(&self, image_pixel: (u32, u32), y: i32, options: &RenderOptions) -> BlockPos {
+        self.map_pixel(image_pixel).block(y, options)
+    }
+}
+
+/// The radius in pixels of the markers drawn on stitched images, not counting their outline.
+const MARKER_RADIUS: i64 = 5;
+/// The outline color of markers drawn on stitched images, so they stand out on both dark and bright terrain.
+const MARKER_OUTLINE_COLOR: [u8; 3] = [255, 255, 255];
+
+/// A dot drawn over a point of interest on a stitched image, like a player or the world spawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Marker {
+    /// The full resolution pixel of the map the marker is centered on, see `RenderOptions::map_pixel`.
+    pub pixel: (i64, i64),
+    /// The fill color of the marker.
+    pub color: [u8; 3],
+}
+
+/// Everything drawn or written over the map when it is stitched, on top of the rendered regions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MapOverlay {
+    /// Markers drawn over the map.
+    pub markers: Vec<Marker>,
+    /// Text saved in the png file as keyword and text pairs, like the name of the world as its "Title".
+    pub labels: Vec<(String, String)>,
+}
+
+/// Returns the color of a marker at an offset from its center, or none if the offset is outside of the marker.
+const fn marker_pixel(marker: &Marker, dx: i64, dy: i64) -> Option<[u8; 3]> {
+    let distance = dx * dx + dy * dy;
+    if distance <= MARKER_RADIUS * MARKER_RADIUS {
+        Some(marker.color)
+    } else if distance <= (MARKER_RADIUS + 1) * (MARKER_RADIUS + 1) {
+        Some(MARKER_OUTLINE_COLOR)
+    } else {
+        None
+    }
+}
+
+/// Returns the area of a saved region image that has anything rendered in it, in full resolution pixels relative to block 0,0.
+pub(crate) fn content_area(region: &RegionImageFile) -> Option<(i64, i64, i64, i64)> {
+    let bounds = region.bounds?;
+    Some((
+        region.origin.0 + bounds.min_x as i64,
+        region.origin.1 + bounds.min_y as i64,
+        region.origin.0 + bounds.max_x as i64,
+        region.origin.1 + bounds.max_y as i64,
+    ))
+}
+
+/// Returns the saved region images that have anything rendered in them, in the order they are drawn, from top to bottom.
+pub fn draw_order(list: &[RegionImageFile]) -> Vec<&RegionImageFile> {
+    let mut ordered: Vec<&RegionImageFile> = list
+        .iter()
+        .filter(|region| region.bounds.is_some())
+        .collect();
+    ordered.sort_by_key(|region| region.origin.1);
+    ordered
+}
+
+/// Stitches saved region images into a single png image, streaming it to disk one row at a time.
+///
+/// The output is cropped to the area that contains rendered chunks, and every region outside of the list is drawn black.
+/// A downscale larger than one keeps only every n-th pixel on each axis, which matches a nearest neighbour resize.
+/// The markers of the overlay are drawn as dots whose size does not change with the downscale, and its labels are saved as png text chunks.
+/// Progress is given to `on_progress` as the number of region images read so far and the number of them in the image.
+/// Returns where the written image sits on the map, or none if there was nothing to stitch.
+pub fn stitch_region_image_files(
+    list: &[RegionImageFile],
+    output: &Path,
+    downscale: u32,
+    overlay: &MapOverlay,
+    on_progress: impl Fn(usize, usize),
+) -> Result<Option<StitchedImage>> {
+    let downscale = downscale.max(1) as i64;
+
+    // area of the world, in full resolution pixels relative to block 0,0, that has something rendered in it.
+    let (min_x, min_y, max_x, max_y) = match list
+        .iter()
+        .filter_map(content_area)
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+    {
+        None => return Ok(None), // nothing was rendered
+        Some(area) => area,
+    };
+
+    let width = ((max_x - min_x) / downscale).max(1) as u32;
+    let height = ((max_y - min_y) / downscale).max(1) as u32;
+    let stitched = StitchedImage {
+        width,
+        height,
+        origin: (min_x, min_y),
+        downscale: downscale as u32,
+    };
+    // markers are placed by the pixel of the output they are centered on, ones cropped out of the image are still drawn where they overlap it.
+    let marker_centers: Vec<(&Marker, i64, i64)> = overlay
+        .markers
+        .iter()
+        .map(|marker| {
+            (
+                marker,
+                (marker.pixel.0 - min_x).div_euclid(downscale),
+                (marker.pixel.1 - min_y).div_euclid(downscale),
+            )
+        })
+        .collect();
+
+    let encode_error = |source| McMapperError::PngEncode {
+        path: output.to_path_buf(),
+        source,
+    };
+    let file = File::create(output).map_err(|err| McMapperError::io(output, err))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, text) in &overlay.labels {
+        encoder
+            .add_itxt_chunk(keyword.clone(), text.clone())
+            .map_err(encode_error)?;
+    }
+    let mut writer = encoder
+        .write_header()
+        .map_err(encode_error)?
+        .into_stream_writer()
+        .map_err(encode_error)?;
+
+    let mut out_row = vec![0u8; width as usize * 3];
+    let regions = draw_order(list);
+    // a reader is opened when the first row of its region is reached and dropped along with its image after the last one.
+    let mut readers: Vec<Option<RegionRowReader>> = regions.iter().map(|_| None).collect();
+    let mut opened_regions = 0; // the number of region images that have been opened so far, used for progress reporting
+
+    for out_y in 0..height as i64 {
+        let pixel_y = min_y + out_y * downscale;
+
+        out_row.iter_mut().for_each(|b| *b = 0);
+        for (region, reader) in regions.iter().zip(readers.iter_mut()) {
+            let local_y = pixel_y - region.origin.1;
+            if local_y < 0 {
+                continue;
+            }
+            if local_y >= region.height as i64 {
+                *reader = None;
+                continue;
+            }
+            if reader.is_none() {
+                *reader = Some(RegionRowReader::open(region)?);
+                opened_regions += 1;
+                if opened_regions % 32 == 0 || opened_regions == regions.len() {
+                    on_progress(opened_regions, regions.len());
+                }
+            }
+            let reader = reader.as_mut().unwrap();
+            let channels = reader.channels;
+            let row = reader.row_at(local_y as u32);
+
+            // the columns of the output that land inside of this region
+            let end_x = region.origin.0 + region.width as i64;
+            let first_out_x =
+                ((region.origin.0 - min_x).max(0) as u64).div_ceil(downscale as u64) as i64;
+            let end_out_x = (((end_x - min_x).max(0) as u64).div_ceil(downscale as u64) as i64)
+                .min(width as i64);
+
+            for out_x in first_out_x..end_out_x {
+                let local_x = (min_x + out_x * downscale - region.origin.0) as usize;
+                let start = local_x * channels;
+                let color = &row[start..start + 3];
+                if color == [0, 0, 0] {
+                    continue; // nothing was drawn here, so whatever is behind it stays visible
+                }
+                let out_start = out_x as usize * 3;
+                out_row[out_start..out_start + 3].copy_from_slice(color);
+            }
+        }
+
+        for (marker, center_x, center_y) in &marker_centers {
+            let dy = out_y - center_y;
+            if dy.abs() > MARKER_RADIUS + 1 {
+                continue;
+            }
+            let first_x = (center_x - MARKER_RADIUS - 1).max(0);
+            let last_x = (center_x + MARKER_RADIUS + 1).min(width as i64 - 1);
+            for out_x in first_x..=last_x {
+                if let Some(color) = marker_pixel(marker, out_x - center_x, dy) {
+                    let out_start = out_x as usize * 3;
+                    out_row[out_start..out_start + 3].copy_from_slice(&color);
+                }
+            }
+        }
+
+        std::io::Write::write_all(&mut writer, &out_row)
+            .map_err(|err| McMapperError::io(output, err))?;
+    }
+
+    writer.finish().map_err(encode_error)?;
+    Ok(Some(stitched))
+}
+
+/// Reads a saved region image one row of pixels at a time.
+struct RegionRowReader {
+    channels: usize,
+    reader: png::Reader<BufReader<File>>,
+    row: Vec<u8>,
+    /// The index of the next row the png reader will return.
+    next_row: u32,
+}
+
+impl RegionRowReader {
+    fn open(region: &RegionImageFile) -> Result<Self> {
+        let file = File::open(&region.path).map_err(|err| McMapperError::io(&region.path, err))?;
+        let mut decoder = png::Decoder::new(BufReader::new(file));
+        decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+        let reader = decoder
+            .read_info()
+            .map_err(|source| McMapperError::PngDecode {
+                path: region.path.clone(),
+                source,
+            })?;
+        let channels = reader.output_color_type().0.samples();
+        Ok(Self {
+            channels,
+            reader,
+            row: vec![0; region.width as usize * channels],
+            next_row: 0,
+        })
+    }
+
+    /// Returns the given row of the region image, rows before it are skipped and rows can not be read twice.
+    /// A black row is returned if the image has no rows left.
+    fn row_at(&mut self, index: u32) -> &[u8] {
+        while self.next_row <= index {
+            match self.reader.next_row() {
+                Ok(Some(row)) => {
+                    let data = row.data();
+                    let len = data.len().min(self.row.len());
+                    self.row[..len].copy_from_slice(&data[..len]);
+                }
+                _ => self.row.iter_mut().for_each(|b| *b = 0),
+            }
+            self.next_row += 1;
+        }
+        &self.row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+    use std::cell::Cell;
+
+    fn region_image(x: i32, z: i32, pixel: (u32, u32), color: [u8; 3]) -> RegionImage {
+        let mut image = RgbImage::new(4, 4);
+        image.put_pixel(pixel.0, pixel.1, Rgb(color));
+        RegionImage {
+            coordinate: ChunkCoordinate(x, z),
+            origin: (x as i64 * 4, z as i64 * 4),
+            image,
+        }
+    }
+
+    #[test]
+    fn content_bounds_cover_every_colored_pixel() {
+        let mut image = RgbImage::new(8, 8);
+        assert_eq!(find_content_bounds(&image), None);
+        image.put_pixel(2, 5, Rgb([10, 0, 0]));
+        image.put_pixel(6, 1, Rgb([0, 10, 0]));
+        assert_eq!(
+            find_content_bounds(&image),
+            Some(ContentBounds {
+                min_x: 2,
+                min_y: 1,
+                max_x: 7,
+                max_y: 6,
+            })
+        );
+    }
+
+    #[test]
+    fn stitched_images_are_cropped_to_their_content() {
+        let dir = std::env::temp_dir().join(format!("mcmapper-stitch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let list = vec![
+            save_region_image(region_image(0, 0, (1, 1), [255, 0, 0]), &dir.join("a.png")).unwrap(),
+            save_region_image(region_image(1, 0, (2, 3), [0, 255, 0]), &dir.join("b.png")).unwrap(),
+        ];
+
+        let progress = Cell::new((0, 0));
+        let output = dir.join("stitched.png");
+        let stitched =
+            stitch_region_image_files(&list, &output, 1, &MapOverlay::default(), |done, total| {
+                progress.set((done, total))
+            })
+            .unwrap();
+        assert_eq!(
+            stitched,
+            Some(StitchedImage {
+                width: 6,
+                height: 3,
+                origin: (1, 1),
+                downscale: 1,
+            })
+        );
+        assert_eq!(progress.get(), (2, 2));
+        let stitched = image::open(&output).unwrap().to_rgb8();
+        assert_eq!(stitched.get_pixel(0, 0), &Rgb([255, 0, 0]));
+        assert_eq!(stitched.get_pixel(5, 2), &Rgb([0, 255, 0]));
+
+        let output = dir.join("downscaled.png");
+        let downscaled =
+            stitch_region_image_files(&list, &output, 2, &MapOverlay::default(), |_, _| {})
+                .unwrap();
+        assert_eq!(
+            downscaled.map(|image| (image.width, image.height)),
+            Some((3, 1))
+        );
+        let downscaled = image::open(&output).unwrap().to_rgb8();
+        assert_eq!(downscaled.get_pixel(0, 0), &Rgb([255, 0, 0]));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,398 @@
+//! Finding the top block of each column in a chunk without checking every block from the top of the world down.
+//!
+//! Chunks store a WORLD_SURFACE heightmap that holds the y level above the highest non air block of each column,
+//! so most columns only need one or two blocks looked at. Chunks without one fall back to the MOTION_BLOCKING heightmap,
+//! and chunks without either, or saved before 1.18, are scanned from the top of the world, skipping sections whose palette only holds air.
+//!
+//! Dimensions with a roof, like the nether, can instead be scanned from under the roof, so the terrain below it is drawn,
+//! and a slice of the world can be drawn by scanning down from a chosen y level.
+
+use crate::chunk::{load_chunk, ChunkError, ChunkProblem, WorldChunk};
+use fastanvil::{expand_heightmap, Block, Chunk, Region};
+use fastnbt::{from_bytes, LongArray};
+use serde::Deserialize;
+use std::io::{Read, Seek};
+
+/// Returns true if the block is any kind of air.
+pub fn is_air(block: &Block) -> bool {
+    matches!(
+        block.name(),
+        "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air"
+    )
+}
+
+/// Returns true if the block can be seen through, like water, ice and glass, so the blocks under it are drawn with it blended over them.
+pub fn is_translucent(block: &Block) -> bool {
+    let name = block.name();
+    matches!(
+        name,
+        "minecraft:water" | "minecraft:bubble_column" | "minecraft:ice" | "minecraft:frosted_ice"
+    ) || name.ends_with("glass")
+        || name.ends_with("glass_pane")
+}
+
+/// Reads a chunk from a region file along with its surface, see `load_chunk`.
+pub fn load_chunk_surface<S: Read + Seek>(
+    region: &mut Region<S>,
+    chunk_x: usize,
+    chunk_z: usize,
+) -> Result<(WorldChunk, ChunkSurface), ChunkError> {
+    let (chunk, data) = load_chunk(region, chunk_x, chunk_z)?;
+    let surface = ChunkSurface::new(&chunk, data.as_slice())?;
+    Ok((chunk, surface))
+}
+
+/// Returns true if fastanvil can expand a heightmap of this many longs saved at this data version.
+///
+/// 1.17 and snapshot 21w44a onwards pack each height into 9 bits, or 10 for worlds taller than 512 blocks, the versions between only into 9 bits.
+const fn heightmap_readable(len: usize, data_version: i32) -> bool {
+    match data_version {
+        2724 | 2730 | 2845.. => matches!(len, 37 | 43),
+        _ => matches!(len, 36 | 37),
+    }
+}
+
+/// Which block of each column is drawn.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum SurfaceMode {
+    /// The highest block that is not air, what is seen from the sky.
+    #[default]
+    Sky,
+    /// The first block under the roof of the world, for dimensions like the nether that are covered in bedrock.
+    BelowCeiling,
+    /// The highest block at or below a y level, for mapping mines and caves that the sky view cannot show.
+    Slice {
+        /// The highest y level a block is drawn from.
+        max_y: isize,
+        /// Only draw blocks that have air directly above them, so solid rock is skipped and only cave and tunnel floors are drawn.
+        air_above_only: bool,
+    },
+}
+
+/// The part of the chunk nbt fastanvil does not read, only used to get the WORLD_SURFACE heightmap.
+#[derive(Deserialize)]
+struct StoredChunkHeightmaps {
+    #[serde(rename = "Heightmaps")]
+    heightmaps: Option<StoredHeightmaps>,
+}
+
+#[derive(Deserialize)]
+struct StoredHeightmaps {
+    #[serde(rename = "WORLD_SURFACE")]
+    world_surface: Option<LongArray>,
+}
+
+/// What is needed to quickly find the surface of each column of a chunk, read once per chunk.
+#[derive(Debug, Clone)]
+pub struct ChunkSurface {
+    /// The y level above the highest block of each column from the stored heightmap, indexed by z * 16 + x.
+    heightmap: Option<Vec<i16>>,
+    /// Whether each section, from the bottom of the world up, holds any block other than air.
+    filled_sections: Vec<bool>,
+    /// The lowest y level a block can be at in this chunk.
+    y_min: isize,
+    /// One past the highest y level a block can be at in this chunk.
+    y_max: isize,
+}
+
+impl ChunkSurface {
+    /// Reads the heightmaps and section palettes of a chunk.
+    ///
+    /// The raw chunk data is needed as well, since fastanvil only reads the MOTION_BLOCKING heightmap.
+    /// A chunk whose heightmap is not packed the way its data version packs them can not be drawn, and is returned as an error.
+    pub fn new(chunk: &WorldChunk, data: &[u8]) -> Result<Self, ChunkError> {
+        let y_range = chunk.y_range();
+
+        // chunks saved before 1.18 are scanned from the top of their highest section instead, they are rarely rendered more than once.
+        let heightmap = match chunk {
+            WorldChunk::Current(chunk) => {
+                let world_surface = from_bytes::<StoredChunkHeightmaps>(data)
+                    .ok()
+                    .and_then(|stored| stored.heightmaps)
+                    .and_then(|heightmaps| heightmaps.world_surface);
+                let motion_blocking = chunk
+                    .heightmaps
+                    .as_ref()
+                    .and_then(|heightmaps| heightmaps.motion_blocking.as_ref());
+                match world_surface.as_ref().or(motion_blocking) {
+                    None => None,
+                    // expand_heightmap panics on a length it does not expect for the data version.
+                    Some(heightmap) if !heightmap_readable(heightmap.len(), chunk.data_version) => {
+                        return Err(ChunkError::new(
+                            ChunkProblem::UnsupportedFormat,
+                            format!(
+                                "a heightmap of {} longs can not be read at data version {}",
+                                heightmap.len(),
+                                chunk.data_version
+                            ),
+                        ));
+                    }
+                    Some(heightmap) => Some(expand_heightmap(
+                        heightmap,
+                        y_range.start,
+                        chunk.data_version,
+                    )),
+                }
+            }
+            WorldChunk::Pre18(_) | WorldChunk::Pre13(_) => None,
+        };
+
+        let filled_sections = y_range
+            .clone()
+            .step_by(16)
+            .map(|y| chunk.section_filled(y))
+            .collect();
+
+        Ok(Self {
+            heightmap,
+            filled_sections,
+            y_min: y_range.start,
+            y_max: y_range.end,
+        })
+    }
+
+    /// Finds the top block of a column that is not air, and returns it along with the y level it was found at.
+    pub fn surface_block<'a>(
+        &self,
+        chunk: &'a WorldChunk,
+        x: usize,
+        z: usize,
+    ) -> Option<(&'a Block, isize)> {
+        let top = match &self.heightmap {
+            Some(heightmap) => {
+                // blocks at and above the heightmap are still checked, the motion blocking heightmap skips things like flowers,
+                // and a heightmap can be out of date if the world was edited by an external tool.
+                let mut y = (heightmap[z * 16 + x] as isize).max(self.y_min);
+                while y < self.y_max && chunk.block(x, y, z).is_some_and(|b| !is_air(b)) {
+                    y += 1;
+                }
+                y
+            }
+            None => self.y_max,
+        };
+        self.block_at_or_below(chunk, x, top - 1, z)
+    }
+
+    /// Finds the block of a column that is drawn in the given surface mode, and returns it along with the y level it was found at.
+    pub fn column_block<'a>(
+        &self,
+        chunk: &'a WorldChunk,
+        x: usize,
+        z: usize,
+        mode: SurfaceMode,
+    ) -> Option<(&'a Block, isize)> {
+        match mode {
+            SurfaceMode::Sky => self.surface_block(chunk, x, z),
+            SurfaceMode::BelowCeiling => self.block_below_ceiling(chunk, x, z),
+            SurfaceMode::Slice {
+                max_y,
+                air_above_only: false,
+            } => self.block_at_or_below(chunk, x, max_y, z),
+            SurfaceMode::Slice {
+                max_y,
+                air_above_only: true,
+            } => self.floor_at_or_below(chunk, x, max_y + 1, z), // the block at max_y counts if the block above the slice is air
+        }
+    }
+
+    /// Finds the first block under the roof of a column, the scan goes down through the roof until it finds a gap of air,
+    /// and the block under the gap is returned. Columns that are solid all the way down have nothing to draw.
+    pub fn block_below_ceiling<'a>(
+        &self,
+        chunk: &'a WorldChunk,
+        x: usize,
+        z: usize,
+    ) -> Option<(&'a Block, isize)> {
+        let (_, roof_top) = self.surface_block(chunk, x, z)?;
+        self.floor_at_or_below(chunk, x, roof_top, z)
+    }
+
+    /// Finds the first block with air directly above it, at or below the given y level.
+    pub fn floor_at_or_below<'a>(
+        &self,
+        chunk: &'a WorldChunk,
+        x: usize,
+        y: isize,
+        z: usize,
+    ) -> Option<(&'a Block, isize)> {
+        let gap = self.air_at_or_below(chunk, x, y, z)?;
+        self.block_at_or_below(chunk, x, gap, z)
+    }
+
+    /// Finds the first block below the given y level that can not be seen through, looking past air and translucent blocks like water.
+    pub fn block_under_translucent<'a>(
+        &self,
+        chunk: &'a WorldChunk,
+        x: usize,
+        y: isize,
+        z: usize,
+    ) -> Option<(&'a Block, isize)> {
+        let mut y = y - 1;
+        loop {
+            let (b, found_y) = self.block_at_or_below(chunk, x, y, z)?;
+            if !is_translucent(b) {
+                return Some((b, found_y));
+            }
+            y = found_y - 1;
+        }
+    }
+
+    /// Finds the first block that is not air at or below the given y level, skipping sections that only hold air.
+    pub fn block_at_or_below<'a>(
+        &self,
+        chunk: &'a WorldChunk,
+        x: usize,
+        y: isize,
+        z: usize,
+    ) -> Option<(&'a Block, isize)> {
+        let mut y = y.min(self.y_max - 1);
+        while y >= self.y_min {
+            if !self.section_filled(y) {
+                y = y.div_euclid(16) * 16 - 1; // jump to the top of the section below
+                continue;
+            }
+            if let Some(b) = chunk.block(x, y, z) {
+                if !is_air(b) {
+                    return Some((b, y));
+                }
+            }
+            y -= 1;
+        }
+        None
+    }
+
+    /// Finds the first air block at or below the given y level, every block above the world or in a section that only holds air is air.
+    fn air_at_or_below(&self, chunk: &WorldChunk, x: usize, y: isize, z: usize) -> Option<isize> {
+        if y >= self.y_max {
+            return Some(y);
+        }
+        let mut y = y;
+        while y >= self.y_min {
+            if !self.section_filled(y) {
+                return Some(y);
+            }
+            match chunk.block(x, y, z) {
+                Some(b) if !is_air(b) => y -= 1,
+                _ => return Some(y),
+            }
+        }
+        None
+    }
+
+    /// The range of y levels blocks can be at in this chunk.
+    pub const fn y_range(&self) -> std::ops::Range<isize> {
+        self.y_min..self.y_max
+    }
+
+    /// Returns whether the section holding the given y level has any block other than air in it.
+    fn section_filled(&self, y: isize) -> bool {
+        self.filled_sections
+            .get(((y - self.y_min) / 16) as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::read_chunk;
+    use fastnbt::Value;
+
+    /// Encodes a chunk whose sections from y 0 up are each filled with one block, with a WORLD_SURFACE heightmap of the given number of longs.
+    fn filled_chunk(blocks: &[&str], data_version: i32, heightmap_len: usize) -> Vec<u8> {
+        let compound = |entries: Vec<(&str, Value)>| {
+            Value::Compound(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.to_string(), value))
+                    .collect(),
+            )
+        };
+        let palette = |entry: Value| compound(vec![("palette", Value::List(vec![entry]))]);
+        let sections = blocks
+            .iter()
+            .enumerate()
+            .map(|(y, name)| {
+                compound(vec![
+                    ("Y", Value::Byte(y as i8)),
+                    (
+                        "block_states",
+                        palette(compound(vec![("Name", Value::String(name.to_string()))])),
+                    ),
+                    (
+                        "biomes",
+                        palette(Value::String("minecraft:plains".to_string())),
+                    ),
+                ])
+            })
+            .collect();
+        let chunk = compound(vec![
+            ("DataVersion", Value::Int(data_version)),
+            ("Status", Value::String("full".to_string())),
+            ("sections", Value::List(sections)),
+            (
+                "Heightmaps",
+                compound(vec![(
+                    "WORLD_SURFACE",
+                    Value::LongArray(LongArray::new(vec![0; heightmap_len])),
+                )]),
+            ),
+        ]);
+        fastnbt::to_bytes(&chunk).unwrap()
+    }
+
+    /// Encodes a chunk holding one section of stone from y 0 to 15.
+    fn stone_chunk(data_version: i32, heightmap_len: usize) -> Vec<u8> {
+        filled_chunk(&["minecraft:stone"], data_version, heightmap_len)
+    }
+
+    fn read_surface(data: &[u8]) -> Result<(WorldChunk, ChunkSurface), ChunkError> {
+        let chunk = read_chunk(data).unwrap();
+        let surface = ChunkSurface::new(&chunk, data)?;
+        Ok((chunk, surface))
+    }
+
+    #[test]
+    fn heightmap_length_must_fit_the_data_version() {
+        // 1.18 worlds pack heights into 9 bits, 37 longs, taller datapack worlds into 10 bits, 43 longs.
+        assert!(heightmap_readable(37, 3120));
+        assert!(heightmap_readable(43, 3120));
+        assert!(!heightmap_readable(52, 3120));
+        // snapshot 21w43a can only be read with 9 bits.
+        assert!(heightmap_readable(37, 2844));
+        assert!(!heightmap_readable(43, 2844));
+        assert!(heightmap_readable(43, 2730));
+        assert!(heightmap_readable(36, 2586));
+    }
+
+    #[test]
+    fn surface_is_found_from_the_heightmap() {
+        let (chunk, surface) = read_surface(&stone_chunk(3120, 37)).unwrap();
+        let (block, y) = surface.surface_block(&chunk, 3, 7).unwrap();
+        assert_eq!(block.name(), "minecraft:stone");
+        assert_eq!(y, 15);
+    }
+
+    #[test]
+    fn chunks_with_a_truncated_heightmap_are_reported() {
+        let err = read_surface(&stone_chunk(3120, 20)).unwrap_err();
+        assert_eq!(err.problem, ChunkProblem::UnsupportedFormat);
+        // expand_heightmap would panic on a 10 bit heightmap at this version.
+        let err = read_surface(&stone_chunk(2844, 43)).unwrap_err();
+        assert_eq!(err.problem, ChunkProblem::UnsupportedFormat);
+    }
+
+    #[test]
+    fn translucent_blocks_are_looked_through() {
+        let data = filled_chunk(&["minecraft:stone", "minecraft:water"], 3120, 37);
+        let (chunk, surface) = read_surface(&data).unwrap();
+        let (block, y) = surface.block_under_translucent(&chunk, 3, 32, 7).unwrap();
+        assert_eq!(block.name(), "minecraft:stone");
+        assert_eq!(y, 15);
+
+        let (water, _) = surface.block_at_or_below(&chunk, 3, 31, 7).unwrap();
+        assert!(is_translucent(water));
+        assert!(!is_translucent(block));
+    }
+}
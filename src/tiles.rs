@@ -0,0 +1,233 @@
+//! Output of the rendered regions as a z/x/y tile pyramid, the same layout slippy maps like leaflet use.
+//!
+//! The highest zoom level holds the region images cut into 256 pixel tiles, every level below it is made by
+//! joining four tiles of the level above it and scaling them down to a single tile.
+//! Tile 0,0 at the highest zoom level always starts at block 0,0 of the world, so tiles west or north of it have negative coordinates.
+
+use crate::error::{McMapperError, Result};
+use crate::isometric::{block_half_width, ISOMETRIC_MAX_Y};
+use crate::stitch::{content_area, draw_order, RegionImageFile};
+use crate::{Projection, RenderOptions};
+use image::imageops::FilterType;
+use image::{imageops, ImageBuffer, RgbImage};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The size in pixels of each side of a tile.
+pub const TILE_SIZE: u32 = 256;
+/// The y level the viewer of an isometric map shows the block position of, sea level, since a pixel shows a whole line of blocks.
+const VIEWER_Y_LEVEL: isize = 62;
+
+/// A summary of a tile pyramid that was written to disk, used to configure the viewer.
+#[derive(Debug, Copy, Clone)]
+pub struct TilePyramid {
+    /// The zoom level that holds tiles at the full resolution of the render, every level below it halves the resolution.
+    pub max_zoom: u32,
+    /// The options the tiles were rendered with, which decide the block position shown at each pixel in the viewer.
+    pub options: RenderOptions,
+    /// The number of tiles written across all zoom levels.
+    pub tile_count: usize,
+    /// The full resolution pixel at the center of the rendered area, relative to block 0,0, which the viewer starts at.
+    pub center: (i64, i64),
+}
+
+/// Returns the path a tile is stored at inside of the tile directory.
+pub fn tile_path(tile_dir: &Path, zoom: u32, x: i32, y: i32) -> PathBuf {
+    tile_dir
+        .join(zoom.to_string())
+        .join(x.to_string())
+        .join(format!("{}.png", y))
+}
+
+/// Cuts the saved region images into tiles and writes them, along with every lower zoom level, into the tile directory.
+///
+/// Tiles that are completely black, which means no chunks were rendered in them, are skipped.
+/// Region images are read back from disk one at a time per thread, so the whole world is never in memory at once.
+/// Regions are drawn in the same order as when stitching, a tile that overlaps a region drawn earlier is read back and drawn over, leaving out black pixels.
+/// Each zoom level and the number of tiles in it are given to `on_zoom_level` once it is written, from the most detailed level down.
+pub fn write_tile_pyramid(
+    list: &[RegionImageFile],
+    tile_dir: &Path,
+    options: &RenderOptions,
+    on_zoom_level: impl Fn(u32, usize),
+) -> Result<TilePyramid> {
+    let tile_size = TILE_SIZE as i64;
+
+    // work out the tile coordinates that the full resolution level spans, so we know how many zoom levels are needed.
+    let (min_x, min_y, max_x, max_y) = list
+        .iter()
+        .filter_map(content_area)
+        .map(|area| {
+            (
+                area.0.div_euclid(tile_size) as i32,
+                area.1.div_euclid(tile_size) as i32,
+                (area.2 + tile_size - 1).div_euclid(tile_size) as i32,
+                (area.3 + tile_size - 1).div_euclid(tile_size) as i32,
+            )
+        })
+        .reduce(|a, b| (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)))
+        .unwrap_or((0, 0, 1, 1));
+    let span = (max_x - min_x).max(max_y - min_y).max(1) as u32;
+    let max_zoom = span.next_power_of_two().trailing_zeros();
+
+    // full resolution level, cut straight out of the region images.
+    // region images at the same depth never share a tile, since region origins are always multiples of the tile size, so each depth is drawn in parallel.
+    let mut current_tiles: HashSet<(i32, i32)> = HashSet::new();
+    for depth in draw_order(list).chunk_by(|a, b| a.origin.1 == b.origin.1) {
+        let written = depth
+            .par_iter()
+            .map(|region| write_region_tiles(region, tile_dir, max_zoom, &current_tiles))
+            .collect::<Result<Vec<Vec<(i32, i32)>>>>()?;
+        current_tiles.extend(written.into_iter().flatten());
+    }
+
+    let mut tile_count = current_tiles.len();
+    on_zoom_level(max_zoom, current_tiles.len());
+
+    // every lower level is made of the level above it, read back from disk so only a handful of tiles are in memory at once.
+    for zoom in (0..max_zoom).rev() {
+        let mut parents: HashMap<(i32, i32), Vec<(i32, i32)>> = HashMap::new();
+        for (x, y) in &current_tiles {
+            parents
+                .entry((x.div_euclid(2), y.div_euclid(2)))
+                .or_default()
+                .push((*x, *y));
+        }
+
+        current_tiles = parents
+            .into_par_iter()
+            .map(|((parent_x, parent_y), children)| {
+                let mut joined: RgbImage = ImageBuffer::new(TILE_SIZE * 2, TILE_SIZE * 2);
+                for (x, y) in children {
+                    let child_path = tile_path(tile_dir, zoom + 1, x, y);
+                    let child = image::open(&child_path)
+                        .map_err(|err| McMapperError::image(&child_path, err))?
+                        .to_rgb8();
+                    imageops::replace(
+                        &mut joined,
+                        &child,
+                        (x - parent_x * 2) as i64 * TILE_SIZE as i64,
+                        (y - parent_y * 2) as i64 * TILE_SIZE as i64,
+                    );
+                }
+                let tile = imageops::resize(&joined, TILE_SIZE, TILE_SIZE, FilterType::Triangle);
+                save_tile(&tile, &tile_path(tile_dir, zoom, parent_x, parent_y))?;
+                Ok((parent_x, parent_y))
+            })
+            .collect::<Result<_>>()?;
+
+        tile_count += current_tiles.len();
+        on_zoom_level(zoom, current_tiles.len());
+    }
+
+    Ok(TilePyramid {
+        max_zoom,
+        options: *options,
+        tile_count,
+        center: (
+            (min_x + max_x) as i64 * TILE_SIZE as i64 / 2,
+            (min_y + max_y) as i64 * TILE_SIZE as i64 / 2,
+        ),
+    })
+}
+
+/// Draws a saved region image into the full resolution tiles it overlaps, returning the tiles that were written.
+///
+/// Tiles in the drawn set already hold regions drawn before this one, so they are read back and drawn over instead of replaced.
+fn write_region_tiles(
+    region: &RegionImageFile,
+    tile_dir: &Path,
+    zoom: u32,
+    drawn: &HashSet<(i32, i32)>,
+) -> Result<Vec<(i32, i32)>> {
+    let area = match content_area(region) {
+        None => return Ok(vec![]), // completely black regions have no tiles to write
+        Some(area) => area,
+    };
+    let region_image = image::open(&region.path)
+        .map_err(|err| McMapperError::image(&region.path, err))?
+        .to_rgb8();
+    let tile_size = TILE_SIZE as i64;
+
+    let mut written = vec![];
+    for y in area.1.div_euclid(tile_size)..(area.3 + tile_size - 1).div_euclid(tile_size) {
+        for x in area.0.div_euclid(tile_size)..(area.2 + tile_size - 1).div_euclid(tile_size) {
+            let (x, y) = (x as i32, y as i32);
+            let path = tile_path(tile_dir, zoom, x, y);
+            let mut tile: RgbImage = if drawn.contains(&(x, y)) {
+                image::open(&path)
+                    .map_err(|err| McMapperError::image(&path, err))?
+                    .to_rgb8()
+            } else {
+                ImageBuffer::new(TILE_SIZE, TILE_SIZE)
+            };
+            let mut changed = false;
+            for tile_y in 0..TILE_SIZE {
+                let local_y = y as i64 * tile_size + tile_y as i64 - region.origin.1;
+                if local_y < 0 || local_y >= region.height as i64 {
+                    continue;
+                }
+                for tile_x in 0..TILE_SIZE {
+                    let local_x = x as i64 * tile_size + tile_x as i64 - region.origin.0;
+                    if local_x < 0 || local_x >= region.width as i64 {
+                        continue;
+                    }
+                    let pixel = region_image.get_pixel(local_x as u32, local_y as u32);
+                    if pixel.0 == [0, 0, 0] {
+                        continue; // nothing was drawn here, so whatever is behind it stays visible
+                    }
+                    tile.put_pixel(tile_x, tile_y, *pixel);
+                    changed = true;
+                }
+            }
+            if !changed {
+                continue;
+            }
+            save_tile(&tile, &path)?;
+            written.push((x, y));
+        }
+    }
+    Ok(written)
+}
+
+/// Writes the index.html viewer into the tile directory, which lets the tiles be panned and zoomed in a browser.
+pub fn write_tile_viewer(tile_dir: &Path, pyramid: &TilePyramid) -> Result<()> {
+    let html = VIEWER_TEMPLATE
+        .replace("{{MAX_ZOOM}}", &pyramid.max_zoom.to_string())
+        .replace("{{TILE_SIZE}}", &TILE_SIZE.to_string())
+        .replace(
+            "{{PROJECTION}}",
+            match pyramid.options.projection {
+                Projection::TopDown => "top-down",
+                Projection::Isometric => "isometric",
+            },
+        )
+        .replace(
+            "{{BLOCK_PIXELS}}",
+            &pyramid.options.block_style.block_pixels().to_string(),
+        )
+        .replace(
+            "{{HALF_WIDTH}}",
+            &block_half_width(pyramid.options.block_style).to_string(),
+        )
+        .replace("{{MAX_Y}}", &ISOMETRIC_MAX_Y.to_string())
+        .replace("{{VIEW_Y}}", &VIEWER_Y_LEVEL.to_string())
+        .replace("{{CENTER_X}}", &pyramid.center.0.to_string())
+        .replace("{{CENTER_Y}}", &pyramid.center.1.to_string());
+    let path = tile_dir.join("index.html");
+    fs::write(&path, html).map_err(|err| McMapperError::io(&path, err))
+}
+
+/// Saves a single tile, creating the zoom and column directories it belongs in.
+fn save_tile(tile: &RgbImage, path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| McMapperError::io(parent, err))?;
+    }
+    tile.save(path)
+        .map_err(|err| McMapperError::image(path, err))
+}
+
+/// A small self contained slippy map viewer, so the tiles can be viewed without any extra downloads.
+const VIEWER_TEMPLATE: &str = include_str!("viewer.html");
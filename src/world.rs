@@ -0,0 +1,279 @@
+//! The metadata of a world, read from the level.dat file in the world folder.
+//!
+//! level.dat is gzipped nbt holding the name of the world, its spawn point, seed, world border, and the version it was last saved in.
+
+use crate::dimension::Dimension;
+use crate::error::{McMapperError, Result};
+use crate::RenderOptions;
+use flate2::read::GzDecoder;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The newest data version the renderer has been tested with, 1.19.4.
+pub const NEWEST_TESTED_DATA_VERSION: i32 = 3337;
+/// The color the world spawn is marked with on stitched images.
+pub const SPAWN_MARKER_COLOR: [u8; 3] = [250, 200, 30];
+
+/// The metadata of a world.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WorldInfo {
+    /// The name of the world, shown in the world list of the game.
+    pub name: String,
+    /// The x, y and z block position of the world spawn.
+    pub spawn: (i32, i32, i32),
+    /// The data version of the game the world was last saved in, none for worlds saved before 1.9.
+    pub data_version: Option<i32>,
+    /// The name of the version the world was last saved in, e.g. "1.19.2", none for worlds saved before 1.9.
+    pub version_name: Option<String>,
+    /// The seed the world was generated with.
+    pub seed: Option<i64>,
+    /// The world border, none if level.dat does not have one.
+    pub border: Option<WorldBorder>,
+}
+
+/// The world border, a square area centered on a point that players can not leave.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WorldBorder {
+    /// The x and z block position of the center of the border.
+    pub center: (f64, f64),
+    /// The width of the border in blocks.
+    pub size: f64,
+}
+
+#[derive(Deserialize)]
+struct StoredLevel {
+    #[serde(rename = "Data")]
+    data: StoredLevelData,
+}
+
+/// The parts of the Data compound of level.dat that describe the world.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StoredLevelData {
+    level_name: String,
+    spawn_x: i32,
+    spawn_y: i32,
+    spawn_z: i32,
+    data_version: Option<i32>,
+    version: Option<StoredVersion>,
+    /// Where the seed is stored before 1.16.
+    random_seed: Option<i64>,
+    /// Where the seed is stored since 1.16.
+    world_gen_settings: Option<StoredWorldGenSettings>,
+    border_center_x: Option<f64>,
+    border_center_z: Option<f64>,
+    border_size: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct StoredVersion {
+    #[serde(rename = "Name")]
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct StoredWorldGenSettings {
+    seed: i64,
+}
+
+/// A world as written to world.json, along with the pixel of the map its spawn is drawn at.
+#[derive(Serialize)]
+struct WorldMarker<'a> {
+    #[serde(flatten)]
+    world: &'a WorldInfo,
+    /// The full resolution pixel the spawn is at, see `RenderOptions::map_pixel`, none for maps of other dimensions than the overworld.
+    spawn_map_pixel: Option<(i64, i64)>,
+}
+
+/// Returns true if a folder is a world folder rather than the region folder inside of one.
+pub fn is_world_folder(path: &Path) -> bool {
+    path.join("level.dat").is_file() || path.join("region").is_dir()
+}
+
+/// Works out the world folder, the region folder to read and the dimension it belongs to from a world path.
+///
+/// The path can be a world folder, in which case the overworld is read unless a dimension is given, or the region folder inside of one.
+/// It can also be the folder of another dimension or the region folder inside of that, e.g. <world>/DIM-1/region, which reads the nether of <world>.
+/// Picking a dimension means the path is always taken to be the world folder.
+pub fn locate_world(
+    path: &Path,
+    dimension: Option<Dimension>,
+) -> Result<(PathBuf, PathBuf, Dimension)> {
+    if !path.is_dir() {
+        return Err(McMapperError::NotAFolder(path.to_path_buf()));
+    }
+    if let Some(dimension) = dimension {
+        return Ok((path.to_path_buf(), dimension.region_dir(path), dimension));
+    }
+    if path.join("level.dat").is_file() {
+        let dimension = Dimension::Overworld;
+        return Ok((path.to_path_buf(), dimension.region_dir(path), dimension));
+    }
+
+    // otherwise the path is the folder holding a region folder, or the region folder itself.
+    let (folder, region_dir) = if path.join("region").is_dir() {
+        (path.to_path_buf(), path.join("region"))
+    } else {
+        let parent = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        (parent, path.to_path_buf())
+    };
+    match dimension_of_folder(&folder) {
+        Some((world_dir, dimension)) => Ok((world_dir, region_dir, dimension)),
+        None => Ok((folder, region_dir, Dimension::Overworld)),
+    }
+}
+
+/// Works out the world folder and dimension of the folder a dimension's region folder is in, from where it sits in the world folder.
+///
+/// E.g. <world>/DIM-1 is the nether of <world>, and <world>/dimensions/mypack/mining is mypack:mining. Returns none for any other folder.
+fn dimension_of_folder(folder: &Path) -> Option<(PathBuf, Dimension)> {
+    let name = folder.file_name()?.to_str()?;
+    let parent = folder.parent()?;
+    match name {
+        "DIM-1" => Some((parent.to_path_buf(), Dimension::Nether)),
+        "DIM1" => Some((parent.to_path_buf(), Dimension::End)),
+        _ => {
+            let dimensions_dir = parent.parent()?;
+            if dimensions_dir.file_name()? != "dimensions" {
+                return None;
+            }
+            let dimension = Dimension::Custom {
+                namespace: parent.file_name()?.to_str()?.to_string(),
+                name: name.to_string(),
+            };
+            Some((dimensions_dir.parent()?.to_path_buf(), dimension))
+        }
+    }
+}
+
+/// Reads the metadata of a world from its level.dat, returns none if the world folder does not have one.
+pub fn read_world_info(world: &Path) -> Result<Option<WorldInfo>> {
+    let path = world.join("level.dat");
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let level: StoredLevel = read_gzipped_nbt(&path)?;
+    let data = level.data;
+
+    let border = match (data.border_center_x, data.border_center_z, data.border_size) {
+        (Some(x), Some(z), Some(size)) => Some(WorldBorder {
+            center: (x, z),
+            size,
+        }),
+        _ => None,
+    };
+    Ok(Some(WorldInfo {
+        name: data.level_name,
+        spawn: (data.spawn_x, data.spawn_y, data.spawn_z),
+        data_version: data.data_version,
+        version_name: data.version.map(|version| version.name),
+        seed: data
+            .world_gen_settings
+            .map(|settings| settings.seed)
+            .or(data.random_seed),
+        border,
+    }))
+}
+
+/// Decompresses and decodes a gzipped nbt file, like level.dat or a playerdata file.
+pub(crate) fn read_gzipped_nbt<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let compressed = fs::read(path).map_err(|err| McMapperError::io(path, err))?;
+    let mut data = vec![];
+    GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut data)
+        .map_err(|err| McMapperError::io(path, err))?;
+    Ok(fastnbt::from_bytes(&data)?)
+}
+
+impl WorldInfo {
+    /// Returns a warning if the world was last saved in a newer version than the renderer has been tested with.
+    /// Chunks of older versions are all readable, see `chunk::read_chunk`.
+    pub fn version_warning(&self) -> Option<String> {
+        let data_version = self.data_version?;
+        if data_version <= NEWEST_TESTED_DATA_VERSION {
+            return None;
+        }
+        Some(format!(
+            "\"{}\" was last saved in {} (data version {}), which is newer than mcmapper-rs has been tested with, some blocks may not render correctly.",
+            self.name,
+            self.version_name.as_deref().unwrap_or("an unknown version"),
+            data_version
+        ))
+    }
+
+    /// Returns the full resolution pixel the world spawn is drawn at, see `RenderOptions::map_pixel`.
+    pub fn spawn_map_pixel(&self, options: &RenderOptions) -> (i64, i64) {
+        let (x, y, z) = self.spawn;
+        // the center of the spawn block
+        options.map_pixel(x as f64 + 0.5, y as f64, z as f64 + 0.5)
+    }
+
+    /// Returns a one line description of the world, used to label the images written for it.
+    pub fn description(&self) -> String {
+        let mut description = format!(
+            "Spawn at {} {} {}",
+            self.spawn.0, self.spawn.1, self.spawn.2
+        );
+        if let Some(seed) = self.seed {
+            description += &format!(", seed {}", seed);
+        }
+        if let Some(version) = &self.version_name {
+            description += &format!(", last saved in {}", version);
+        }
+        if let Some(border) = &self.border {
+            description += &format!(
+                ", world border {} wide centered on {} {}",
+                border.size, border.center.0, border.center.1
+            );
+        }
+        description
+    }
+}
+
+/// Writes the world metadata to a json file for the tile viewer, along with the pixel of the map the spawn is at if the rendered dimension is the overworld.
+pub fn write_world_json(
+    path: &Path,
+    world: &WorldInfo,
+    dimension: &Dimension,
+    options: &RenderOptions,
+) -> Result<()> {
+    let marker = WorldMarker {
+        world,
+        spawn_map_pixel: (*dimension == Dimension::Overworld)
+            .then(|| world.spawn_map_pixel(options)),
+    };
+    let json =
+        serde_json::to_string_pretty(&marker).map_err(|err| McMapperError::json(path, err))?;
+    fs::write(path, json).map_err(|err| McMapperError::io(path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dimension_folders_belong_to_their_world() {
+        assert_eq!(
+            dimension_of_folder(Path::new("saves/world/DIM-1")),
+            Some((PathBuf::from("saves/world"), Dimension::Nether))
+        );
+        assert_eq!(
+            dimension_of_folder(Path::new("saves/world/DIM1")),
+            Some((PathBuf::from("saves/world"), Dimension::End))
+        );
+        assert_eq!(
+            dimension_of_folder(Path::new("saves/world/dimensions/mypack/mining")),
+            Some((
+                PathBuf::from("saves/world"),
+                Dimension::Custom {
+                    namespace: "mypack".to_string(),
+                    name: "mining".to_string(),
+                }
+            ))
+        );
+        assert_eq!(dimension_of_folder(Path::new("saves/world")), None);
+    }
+}